@@ -1,6 +1,25 @@
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::env;
+use std::os::raw::{c_int, c_ulong};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Set when the output can't show colors; escapes are then stripped
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+// Set by the SIGINT/SIGTERM handler to end --demo
+static STOP: AtomicBool = AtomicBool::new(false);
+
+macro_rules! out {
+    ($($arg:tt)*) => { emit(&format!($($arg)*), false) };
+}
+
+macro_rules! outln {
+    () => { emit("", true) };
+    ($($arg:tt)*) => { emit(&format!($($arg)*), true) };
+}
 
 const HELP: &str = "\
 Terminal Colors Utility
@@ -13,14 +32,40 @@ Options:
     -e, --extended  Show extended colors (8-15)
     -2, --256       Show 256 color palette
     -r, --rgb       Show RGB color examples
+        --fg        With --rgb, color the text of the swatches (38;2)
+                    instead of their background (48;2, the default)
     -f, --format    Show text formatting options
+        --blocks    Draw colors as solid blocks instead of numbered cells
+        --width <N> Width of each block in characters (default: 4)
     -t, --test     'Hello World' in different styles
+        --bg        With --test, also show it on each of the 16 background colors;
+                    with --rgb, fill the swatches (undoes an earlier --fg)
+    -c, --compare <C1> <C2>
+                    Compare two colors (e.g. \"#ff0000\" \"#ee1111\")
+        --random <N>
+                    Generate a palette of N random colors
+        --seed <S>  Seed for --random, to get the same palette again
+        --demo      Animate a truecolor plasma until Ctrl-C (needs a terminal)
+        --no-color  Print labels only, without escape sequences
+        --force-color
+                    Emit colors even when the output doesn't look like a
+                    color terminal (pipe, TERM=dumb or NO_COLOR set)
     -h, --help      Show this help message
 
 Example:
     colors -b -f    Show basic colors and formatting
     colors -2       Show 256 color palette
+    colors -2 --blocks --width 2
+                    Show the 256 color palette as narrow blocks
     colors --test   Show test patterns
+    colors -t --bg  Show test patterns on every background color
+    colors -r --fg  Preview the RGB examples as text colors
+    colors --compare \"#ff0000\" \"#ee1111\"
+    colors --random 8 --seed 42
+                    Show a reproducible random palette
+    colors --demo   Check how smoothly the terminal redraws truecolor
+    colors --force-color | less -R
+                    Keep the colors when paging
 ";
 
 struct Config {
@@ -30,6 +75,23 @@ struct Config {
     show_rgb: bool,
     show_format: bool,
     show_test: bool,
+    test_backgrounds: bool,
+    rgb_foreground: bool,
+    blocks: bool,
+    block_width: usize,
+    compare: Option<(Rgb, Rgb)>,
+    random: Option<usize>,
+    seed: Option<u64>,
+    demo: bool,
+    no_color: bool,
+    force_color: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Rgb {
+    r: u8,
+    g: u8,
+    b: u8,
 }
 
 impl Default for Config {
@@ -41,109 +103,202 @@ impl Default for Config {
             show_rgb: false,
             show_format: false,
             show_test: false,
+            test_backgrounds: false,
+            rgb_foreground: false,
+            blocks: false,
+            block_width: 4,
+            compare: None,
+            random: None,
+            seed: None,
+            demo: false,
+            no_color: false,
+            force_color: false,
         }
     }
 }
 
+fn emit(text: &str, newline: bool) {
+    let text = if PLAIN.load(Ordering::Relaxed) {
+        strip_escapes(text)
+    } else {
+        text.to_string()
+    };
+    if newline {
+        println!("{}", text);
+    } else {
+        print!("{}", text);
+    }
+}
+
+// Drops CSI sequences (ESC [ params final-byte), keeping everything else
+fn strip_escapes(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            plain.push(c);
+            continue;
+        }
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    plain
+}
+
+// Why colors would be wasted on this output, if they would be
+fn color_unsupported() -> Option<&'static str> {
+    if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        return Some("NO_COLOR is set");
+    }
+    if env::var("TERM").is_ok_and(|t| t == "dumb") {
+        return Some("TERM=dumb");
+    }
+    if !io::stdout().is_terminal() {
+        return Some("output is not a terminal");
+    }
+    None
+}
+
 fn print_header(title: &str) {
-    println!("\n{}\n{}", title, "=".repeat(title.len()));
+    outln!("\n{}\n{}", title, "=".repeat(title.len()));
 }
 
-fn show_basic_colors() {
+// One palette entry: a numbered cell on the background color `bg`, or with
+// `blocks` a run of that many full blocks in the foreground color `fg`
+fn cell(fg: &str, bg: &str, label: &str, blocks: Option<usize>) -> String {
+    match blocks {
+        Some(width) => format!("\x1b[{}m{}\x1b[0m", fg, "\u{2588}".repeat(width)),
+        None => format!("\x1b[{}m {} \x1b[0m", bg, label),
+    }
+}
+
+fn show_basic_colors(blocks: Option<usize>) {
     print_header("Basic Colors (0-7)");
+
+    // Foreground and background swatches would look the same
+    if blocks.is_some() {
+        out!("Colors:     ");
+        for i in 0..8 {
+            out!("{}", cell(&(30 + i).to_string(), "", "", blocks));
+        }
+        outln!();
+        return;
+    }
     
     // Foreground colors
-    print!("Foreground: ");
+    out!("Foreground: ");
     for i in 30..38 {
-        print!("\x1b[{}m {:02} \x1b[0m", i, i-30);
+        out!("\x1b[{}m {:02} \x1b[0m", i, i-30);
     }
-    println!();
+    outln!();
     
     // Background colors
-    print!("Background: ");
+    out!("Background: ");
     for i in 40..48 {
-        print!("\x1b[{}m {:02} \x1b[0m", i, i-40);
+        out!("\x1b[{}m {:02} \x1b[0m", i, i-40);
     }
-    println!();
+    outln!();
 }
 
-fn show_extended_colors() {
+fn show_extended_colors(blocks: Option<usize>) {
     print_header("Extended Colors (8-15)");
+
+    if blocks.is_some() {
+        out!("Colors:     ");
+        for i in 0..8 {
+            out!("{}", cell(&(90 + i).to_string(), "", "", blocks));
+        }
+        outln!();
+        return;
+    }
     
     // Foreground colors
-    print!("Foreground: ");
+    out!("Foreground: ");
     for i in 90..98 {
-        print!("\x1b[{}m {:02} \x1b[0m", i, i-90);
+        out!("\x1b[{}m {:02} \x1b[0m", i, i-90);
     }
-    println!();
+    outln!();
     
     // Background colors
-    print!("Background: ");
+    out!("Background: ");
     for i in 100..108 {
-        print!("\x1b[{}m {:02} \x1b[0m", i, i-100);
+        out!("\x1b[{}m {:02} \x1b[0m", i, i-100);
     }
-    println!();
+    outln!();
 }
 
-fn show_256_colors() {
+fn show_256_colors(blocks: Option<usize>) {
     print_header("256 Color Mode");
     
     // Standard colors (0-15)
-    println!("Standard colors:");
+    outln!("Standard colors:");
     for i in 0..16 {
-        print!("\x1b[48;5;{}m {:3} \x1b[0m", i, i);
-        if (i + 1) % 8 == 0 { println!(); }
+        out!("{}", cell(&format!("38;5;{}", i), &format!("48;5;{}", i), &format!("{:3}", i), blocks));
+        if (i + 1) % 8 == 0 { outln!(); }
     }
     
     // Color cube (16-231)
-    println!("\nColor cube:");
+    outln!("\nColor cube:");
     for i in 0..6 {
         for j in 0..6 {
             for k in 0..6 {
                 let color = 16 + (36 * i) + (6 * j) + k;
-                print!("\x1b[48;5;{}m {:3} \x1b[0m", color, color);
+                out!("{}", cell(&format!("38;5;{}", color), &format!("48;5;{}", color),
+                                  &format!("{:3}", color), blocks));
             }
-            print!(" ");
+            out!(" ");
         }
-        println!();
+        outln!();
     }
     
     // Grayscale (232-255)
-    println!("\nGrayscale:");
+    outln!("\nGrayscale:");
     for i in 232..256 {
-        print!("\x1b[48;5;{}m {:3} \x1b[0m", i, i);
-        if (i + 1) % 8 == 0 { println!(); }
+        out!("{}", cell(&format!("38;5;{}", i), &format!("48;5;{}", i), &format!("{:3}", i), blocks));
+        if (i + 1) % 8 == 0 { outln!(); }
     }
-    println!();
+    outln!();
 }
 
-fn show_rgb_colors() {
+// A truecolor cell, colored through the text (`foreground`) or the fill
+fn rgb_cell(r: u8, g: u8, b: u8, label: &str, foreground: bool, blocks: Option<usize>) -> String {
+    let fg = format!("38;2;{};{};{}", r, g, b);
+    let bg = if foreground { fg.clone() } else { format!("48;2;{};{};{}", r, g, b) };
+    cell(&fg, &bg, label, blocks)
+}
+
+fn show_rgb_colors(blocks: Option<usize>, foreground: bool) {
     print_header("RGB Color Examples");
     
     // RGB color gradients
-    println!("Red gradient:");
+    outln!("Red gradient:");
     for i in 0..8 {
         let val = i * 31;
-        print!("\x1b[48;2;{};0;0m {:3} \x1b[0m", val, val);
+        out!("{}", rgb_cell(val, 0, 0, &format!("{:3}", val), foreground, blocks));
     }
-    println!();
+    outln!();
     
-    println!("Green gradient:");
+    outln!("Green gradient:");
     for i in 0..8 {
         let val = i * 31;
-        print!("\x1b[48;2;0;{};0m {:3} \x1b[0m", val, val);
+        out!("{}", rgb_cell(0, val, 0, &format!("{:3}", val), foreground, blocks));
     }
-    println!();
+    outln!();
     
-    println!("Blue gradient:");
+    outln!("Blue gradient:");
     for i in 0..8 {
         let val = i * 31;
-        print!("\x1b[48;2;0;0;{}m {:3} \x1b[0m", val, val);
+        out!("{}", rgb_cell(0, 0, val, &format!("{:3}", val), foreground, blocks));
     }
-    println!();
+    outln!();
     
     // Some predefined RGB colors
-    println!("\nSome RGB colors:");
+    outln!("\nSome RGB colors:");
     let colors = [
         (255, 0, 0, "Red"),
         (0, 255, 0, "Green"),
@@ -154,9 +309,9 @@ fn show_rgb_colors() {
     ];
     
     for (r, g, b, name) in colors.iter() {
-        print!("\x1b[48;2;{};{};{}m {} \x1b[0m ", r, g, b, name);
+        out!("{} ", rgb_cell(*r, *g, *b, name, foreground, blocks));
     }
-    println!();
+    outln!();
 }
 
 fn show_formatting() {
@@ -173,47 +328,300 @@ fn show_formatting() {
     ];
     
     for (code, name) in formats.iter() {
-        println!("\x1b[{}m{:<15}\x1b[0m - \\x1b[{}m", code, name, code);
+        outln!("\x1b[{}m{:<15}\x1b[0m - \\x1b[{}m", code, name, code);
     }
 }
 
-fn show_test_patterns() {
+fn show_test_patterns(backgrounds: bool) {
     print_header("Test Patterns");
     
     let text = "Hello, World!";
     
     // Different styles
-    println!("Normal:          {}", text);
-    println!("Bold:            \x1b[1m{}\x1b[0m", text);
-    println!("Dim:             \x1b[2m{}\x1b[0m", text);
-    println!("Italic:          \x1b[3m{}\x1b[0m", text);
-    println!("Underline:       \x1b[4m{}\x1b[0m", text);
-    println!("Blink:           \x1b[5m{}\x1b[0m", text);
-    println!("Reverse:         \x1b[7m{}\x1b[0m", text);
-    println!("Hidden:          \x1b[8m{}\x1b[0m (hidden)", text);
-    println!("Strikethrough:   \x1b[9m{}\x1b[0m", text);
+    outln!("Normal:          {}", text);
+    outln!("Bold:            \x1b[1m{}\x1b[0m", text);
+    outln!("Dim:             \x1b[2m{}\x1b[0m", text);
+    outln!("Italic:          \x1b[3m{}\x1b[0m", text);
+    outln!("Underline:       \x1b[4m{}\x1b[0m", text);
+    outln!("Blink:           \x1b[5m{}\x1b[0m", text);
+    outln!("Reverse:         \x1b[7m{}\x1b[0m", text);
+    outln!("Hidden:          \x1b[8m{}\x1b[0m (hidden)", text);
+    outln!("Strikethrough:   \x1b[9m{}\x1b[0m", text);
     
     // Color combinations
-    println!("\nColor combinations:");
-    println!("Red on White:    \x1b[31;47m{}\x1b[0m", text);
-    println!("Blue on Yellow:  \x1b[34;43m{}\x1b[0m", text);
-    println!("White on Blue:   \x1b[37;44m{}\x1b[0m", text);
-    println!("Yellow on Red:   \x1b[33;41m{}\x1b[0m", text);
+    outln!("\nColor combinations:");
+    outln!("Red on White:    \x1b[31;47m{}\x1b[0m", text);
+    outln!("Blue on Yellow:  \x1b[34;43m{}\x1b[0m", text);
+    outln!("White on Blue:   \x1b[37;44m{}\x1b[0m", text);
+    outln!("Yellow on Red:   \x1b[33;41m{}\x1b[0m", text);
+
+    if backgrounds {
+        const NAMES: [&str; 8] = ["Black", "Red", "Green", "Yellow", "Blue", "Magenta", "Cyan", "White"];
+
+        // Black or white text, whichever reads better on the usual xterm color
+        outln!("\nBackgrounds:");
+        for index in 0..16u8 {
+            let code = if index < 8 { 40 + index as u32 } else { 100 + index as u32 - 8 };
+            let foreground = if relative_luminance(palette_rgb(index)) > 0.179 { 30 } else { 97 };
+            let name = if index < 8 {
+                NAMES[index as usize].to_string()
+            } else {
+                format!("Bright {}", NAMES[index as usize - 8])
+            };
+            outln!("{:<17}\x1b[{};{}m {} \x1b[0m", format!("{}:", name), foreground, code, text);
+        }
+    }
+}
+
+// Parse "#rrggbb", "rrggbb" or the short "#rgb" form
+fn parse_color(s: &str) -> Option<Rgb> {
+    let hex = s.trim_start_matches('#');
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| vec![c, c]).collect(),
+        6 => hex.to_string(),
+        _ => return None,
+    };
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Rgb { r: channel(0)?, g: channel(2)?, b: channel(4)? })
+}
+
+// Undo the sRGB gamma curve
+fn linear_channel(c: u8) -> f64 {
+    let c = c as f64 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+// WCAG relative luminance, 0.0 for black to 1.0 for white
+fn relative_luminance(color: Rgb) -> f64 {
+    0.2126 * linear_channel(color.r) + 0.7152 * linear_channel(color.g) + 0.0722 * linear_channel(color.b)
+}
+
+// Convert sRGB to CIE L*a*b* (D65 white point)
+fn srgb_to_lab(color: Rgb) -> (f64, f64, f64) {
+    let (r, g, b) = (linear_channel(color.r), linear_channel(color.g), linear_channel(color.b));
+
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = relative_luminance(color);
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f64| {
+        if t > 216.0 / 24389.0 { t.cbrt() } else { (24389.0 / 27.0 * t + 16.0) / 116.0 }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+// CIE76 color difference
+fn delta_e(a: Rgb, b: Rgb) -> f64 {
+    let (l1, a1, b1) = srgb_to_lab(a);
+    let (l2, a2, b2) = srgb_to_lab(b);
+    ((l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2)).sqrt()
+}
+
+// RGB value of a 256-color palette entry. Entries 0-15 depend on the
+// terminal's theme; xterm's defaults are used for them.
+fn palette_rgb(index: u8) -> Rgb {
+    const XTERM: [(u8, u8, u8); 16] = [
+        (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+        (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+        (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+        (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+    ];
+    if index < 16 {
+        let (r, g, b) = XTERM[index as usize];
+        return Rgb { r, g, b };
+    }
+    if index >= 232 {
+        let v = 8 + (index - 232) * 10;
+        return Rgb { r: v, g: v, b: v };
+    }
+    let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+    let i = index - 16;
+    Rgb { r: level(i / 36), g: level((i / 6) % 6), b: level(i % 6) }
+}
+
+// Nearest 256-color index; 0-15 are skipped since terminals theme them
+fn nearest_256(color: Rgb) -> u8 {
+    (16..=255u8)
+        .min_by(|&a, &b| {
+            delta_e(color, palette_rgb(a)).partial_cmp(&delta_e(color, palette_rgb(b))).unwrap()
+        })
+        .unwrap_or(16)
+}
+
+fn show_comparison(first: Rgb, second: Rgb) {
+    print_header("Color Comparison");
+
+    let swatch = " ".repeat(20);
+    for _ in 0..3 {
+        outln!("  \x1b[48;2;{};{};{}m{}\x1b[0m    \x1b[48;2;{};{};{}m{}\x1b[0m",
+                 first.r, first.g, first.b, swatch,
+                 second.r, second.g, second.b, swatch);
+    }
+    outln!();
+
+    for (label, color) in [("First", first), ("Second", second)].iter() {
+        let index = nearest_256(*color);
+        outln!("{:<7} #{:02x}{:02x}{:02x}  rgb({:3}, {:3}, {:3})  nearest 256: \x1b[48;5;{}m {:3} \x1b[0m",
+                 label, color.r, color.g, color.b, color.r, color.g, color.b, index, index);
+    }
+
+    let difference = delta_e(first, second);
+    let verdict = if difference < 1.0 {
+        "not perceptible"
+    } else if difference < 2.3 {
+        "barely perceptible"
+    } else if difference < 10.0 {
+        "perceptible at a glance"
+    } else {
+        "clearly different"
+    };
+    outln!("\nDelta E (CIE76): {:.2} - {}", difference, verdict);
+}
+
+// Small xorshift64* generator, so a palette can be reproduced from its seed
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck on a zero state
+        let state = seed ^ 0x9e37_79b9_7f4a_7c15;
+        Rng(if state == 0 { 1 } else { state })
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+fn show_random(count: usize, seed: u64) {
+    print_header(&format!("Random Palette (seed {})", seed));
+    outln!("Truecolor, then the nearest 256-color entry");
+
+    let mut rng = Rng::new(seed);
+    for _ in 0..count {
+        let bits = rng.next();
+        let color = Rgb { r: (bits >> 16) as u8, g: (bits >> 8) as u8, b: bits as u8 };
+        let index = nearest_256(color);
+        outln!("\x1b[48;2;{};{};{}m      \x1b[0m \x1b[48;5;{}m      \x1b[0m  #{:02x}{:02x}{:02x}  rgb({:3}, {:3}, {:3})  256: {:3}",
+               color.r, color.g, color.b, index,
+               color.r, color.g, color.b, color.r, color.g, color.b, index);
+    }
+}
+
+extern "C" {
+    fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+}
+
+// struct winsize, filled in by TIOCGWINSZ
+#[repr(C)]
+#[derive(Default)]
+struct WinSize {
+    rows: u16,
+    cols: u16,
+    x_pixels: u16,
+    y_pixels: u16,
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd",
+          target_os = "openbsd", target_os = "netbsd", target_os = "dragonfly"))]
+const TIOCGWINSZ: c_ulong = 0x4008_7468;
+#[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "freebsd",
+              target_os = "openbsd", target_os = "netbsd", target_os = "dragonfly")))]
+const TIOCGWINSZ: c_ulong = 0x5413;
+
+extern "C" fn request_stop(_: c_int) {
+    STOP.store(true, Ordering::SeqCst);
+}
+
+// Terminal size as the terminal on stdout reports it. Shells set COLUMNS
+// and LINES without exporting them, so those only serve as a fallback.
+fn terminal_size() -> (usize, usize) {
+    let mut size = WinSize::default();
+    if unsafe { ioctl(1, TIOCGWINSZ, &mut size as *mut WinSize) } == 0 && size.cols > 0 && size.rows > 0 {
+        return (size.cols as usize, size.rows as usize);
+    }
+    let get = |name: &str, default: usize| env::var(name).ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|&value: &usize| value > 0)
+        .unwrap_or(default);
+    (get("COLUMNS", 80), get("LINES", 24))
+}
+
+// Plasma drawn with upper half blocks, so each character cell holds two
+// pixels: the top one as foreground, the bottom one as background
+fn run_demo() {
+    const FRAME: Duration = Duration::from_millis(33);
+    const SIGINT: c_int = 2;
+    const SIGTERM: c_int = 15;
+
+    unsafe {
+        signal(SIGINT, request_stop);
+        signal(SIGTERM, request_stop);
+    }
+
+    let (width, height) = terminal_size();
+    // The last line is left for the status
+    let rows = height.saturating_sub(1).max(1);
+    let pixel = |x: f64, y: f64, t: f64| {
+        let v = (x / 8.0 + t).sin()
+            + (y / 6.0 + t * 1.3).sin()
+            + ((x + y) / 10.0 + t * 0.7).sin()
+            + ((x * x + y * y).sqrt() / 6.0 - t).sin();
+        let phase = std::f64::consts::PI * v / 2.0;
+        let channel = |shift: f64| (128.0 + 127.0 * (phase + shift).sin()) as u8;
+        (channel(0.0), channel(2.094), channel(4.189))
+    };
+
+    let mut stdout = io::stdout().lock();
+    // Hide the cursor and clear the screen
+    let _ = write!(stdout, "\x1b[?25l\x1b[2J");
+    let started = Instant::now();
+    let mut frames = 0u64;
+    while !STOP.load(Ordering::SeqCst) {
+        let frame_start = Instant::now();
+        let t = started.elapsed().as_secs_f64();
+        let mut frame = String::from("\x1b[H");
+        for row in 0..rows {
+            for x in 0..width {
+                let (r1, g1, b1) = pixel(x as f64, (row * 2) as f64, t);
+                let (r2, g2, b2) = pixel(x as f64, (row * 2 + 1) as f64, t);
+                frame.push_str(&format!("\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                                        r1, g1, b1, r2, g2, b2));
+            }
+            frame.push_str("\x1b[0m\r\n");
+        }
+        frames += 1;
+        let fps = frames as f64 / started.elapsed().as_secs_f64().max(0.001);
+        frame.push_str(&format!("\x1b[0m\x1b[KFrame {}, {:.1} fps - Ctrl-C to quit", frames, fps));
+        if stdout.write_all(frame.as_bytes()).and_then(|_| stdout.flush()).is_err() {
+            break;
+        }
+        if let Some(rest) = FRAME.checked_sub(frame_start.elapsed()) {
+            thread::sleep(rest);
+        }
+    }
+
+    // Reset colors, show the cursor again and leave a clean screen
+    let _ = write!(stdout, "\x1b[0m\x1b[?25h\x1b[2J\x1b[H");
+    let _ = stdout.flush();
 }
 
 fn parse_args() -> Config {
     let args: Vec<String> = env::args().collect();
     let mut config = Config::default();
     
-    // If no arguments are given, show everything
-    if args.len() == 1 {
-        config.show_basic = true;
-        config.show_extended = true;
-        config.show_format = true;
-        return config;
-    }
-    
-    for arg in args.iter().skip(1) {
+    let mut i = 1;
+    while i < args.len() {
+        let arg = &args[i];
         match arg.as_str() {
             "-b" | "--basic" => config.show_basic = true,
             "-e" | "--extended" => config.show_extended = true,
@@ -221,6 +629,62 @@ fn parse_args() -> Config {
             "-r" | "--rgb" => config.show_rgb = true,
             "-f" | "--format" => config.show_format = true,
             "-t" | "--test" => config.show_test = true,
+            "--bg" => {
+                config.test_backgrounds = true;
+                config.rgb_foreground = false;
+            }
+            "--fg" => config.rgb_foreground = true,
+            "--blocks" => config.blocks = true,
+            "--random" => {
+                let count = args.get(i + 1).and_then(|n| n.parse::<usize>().ok());
+                match count {
+                    Some(count) if count > 0 => config.random = Some(count),
+                    _ => {
+                        eprintln!("Error: --random needs a positive number of colors");
+                        process::exit(1);
+                    }
+                }
+                i += 1;
+            }
+            "--seed" => {
+                match args.get(i + 1).and_then(|s| s.parse::<u64>().ok()) {
+                    Some(seed) => config.seed = Some(seed),
+                    None => {
+                        eprintln!("Error: --seed needs a number");
+                        process::exit(1);
+                    }
+                }
+                i += 1;
+            }
+            "--demo" => config.demo = true,
+            "--no-color" => config.no_color = true,
+            "--force-color" => config.force_color = true,
+            "--width" => {
+                let width = args.get(i + 1).and_then(|w| w.parse::<usize>().ok());
+                match width {
+                    Some(width) if width > 0 => config.block_width = width,
+                    _ => {
+                        eprintln!("Error: --width needs a positive number");
+                        process::exit(1);
+                    }
+                }
+                i += 1;
+            }
+            "-c" | "--compare" => {
+                if i + 2 >= args.len() {
+                    eprintln!("Error: --compare needs two colors");
+                    process::exit(1);
+                }
+                let colors = (parse_color(&args[i + 1]), parse_color(&args[i + 2]));
+                match colors {
+                    (Some(first), Some(second)) => config.compare = Some((first, second)),
+                    _ => {
+                        eprintln!("Error: Invalid color (expected #rrggbb)");
+                        process::exit(1);
+                    }
+                }
+                i += 2;
+            }
             "-h" | "--help" => {
                 println!("{}", HELP);
                 process::exit(0);
@@ -231,6 +695,18 @@ fn parse_args() -> Config {
                 process::exit(1);
             }
         }
+        i += 1;
+    }
+    
+    // Without a section to show, the default view is shown; options such as
+    // --no-color or --blocks only change how it looks
+    let sections = [config.show_basic, config.show_extended, config.show_256, config.show_rgb,
+                    config.show_format, config.show_test, config.compare.is_some(),
+                    config.random.is_some(), config.demo];
+    if !sections.contains(&true) {
+        config.show_basic = true;
+        config.show_extended = true;
+        config.show_format = true;
     }
     
     config
@@ -238,21 +714,58 @@ fn parse_args() -> Config {
 
 fn main() {
     let config = parse_args();
+
+    if config.seed.is_some() && config.random.is_none() {
+        eprintln!("Error: --seed only applies to --random");
+        process::exit(1);
+    }
+    if config.no_color && config.force_color {
+        eprintln!("Error: --no-color and --force-color can't be used together");
+        process::exit(1);
+    }
+    if config.demo && !io::stdout().is_terminal() {
+        eprintln!("Error: --demo needs a terminal to draw in");
+        process::exit(1);
+    }
+    let plain = if config.no_color {
+        true
+    } else if config.force_color {
+        false
+    } else if let Some(reason) = color_unsupported() {
+        eprintln!("Note: {}, showing labels only (use --force-color to keep colors)", reason);
+        true
+    } else {
+        false
+    };
+    PLAIN.store(plain, Ordering::Relaxed);
+
+    // The animation takes over the whole terminal
+    if config.demo {
+        if plain {
+            eprintln!("Error: --demo needs color output");
+            process::exit(1);
+        }
+        run_demo();
+        return;
+    }
+
+    // Blocks carry no label, so they'd be blank without color
+    let blocks = if config.blocks && !plain { Some(config.block_width) } else { None };
     
     if config.show_basic {
-        show_basic_colors();
+        show_basic_colors(blocks);
     }
     
     if config.show_extended {
-        show_extended_colors();
+        show_extended_colors(blocks);
     }
     
     if config.show_256 {
-        show_256_colors();
+        show_256_colors(blocks);
     }
     
     if config.show_rgb {
-        show_rgb_colors();
+        show_rgb_colors(blocks, config.rgb_foreground);
     }
     
     if config.show_format {
@@ -260,10 +773,23 @@ fn main() {
     }
     
     if config.show_test {
-        show_test_patterns();
+        show_test_patterns(config.test_backgrounds);
+    }
+
+    if let Some((first, second)) = config.compare {
+        show_comparison(first, second);
+    }
+
+    if let Some(count) = config.random {
+        let seed = config.seed.unwrap_or_else(|| {
+            SystemTime::now().duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(0)
+        });
+        show_random(count, seed);
     }
     
     // Make sure all color attributes are reset
-    print!("\x1b[0m");
+    out!("\x1b[0m");
     io::stdout().flush().unwrap();
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
 use std::env;
+use std::fs;
 use std::process;
+use std::collections::HashSet;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 const HELP: &str = r#"
@@ -7,28 +9,84 @@ DateDiff - Date and Time Difference Calculator
 
 Usage:
     datediff [OPTIONS] <date1> [date2]
+    datediff --span [OPTIONS] <date> <date> [date...]
+    datediff --humanize [OPTIONS] <duration>
 
 Options:
     -h, --help          Show this help message
     -n, --now          Use current time as second date
     -u, --unit <unit>  Output unit (years|months|days|hours|minutes|seconds)
     -f, --format       Format output as detailed breakdown
+    -a, --all-units    Print the whole difference in every unit, years
+                       down to seconds, one per line
     -s, --simple       Simple output (only numbers)
+    -t, --template <t> Print the template with placeholders filled in
+                       (also --format-string; see Template Placeholders)
+    -r, --round <n>    Decimal places for fractional units (default: 2)
+    -p, --precision <p>
+                       Smallest unit of the difference: s (default), ms, us
+                       or ns; seconds then carry that many fractional digits
+    -v, --verbose      Also print the resolved dates
+    --display-tz <tz>  Offset for showing resolved dates, e.g. +09:00 (default: UTC)
+    -b, --business-days
+                       Count weekdays (Mon-Fri) from date1 up to, not including, date2
+    --strict           Reject 24:00:00 and leap seconds (:60) instead of
+                       normalizing them
+    --holidays <file>  Also skip the YYYY-MM-DD dates listed in file, one per line
+                       (implies --business-days)
+    --span             Take any number of dates and measure from the earliest
+                       to the latest of them
+    -g, --guess        Also accept other common date formats (see below)
+    --humanize <d>     Break down a duration instead of diffing dates (also
+                       --duration): seconds ("90061") or amounts with units
+                       ("1d 2h 3m", "2w3d"); y, mo, w, d, h, m, s or the
+                       full unit names. Shown like --format unless
+                       -u, -a or -s asks otherwise
 
 Date Formats:
     YYYY-MM-DD
     YYYY-MM-DD HH:MM:SS
+    YYYY-MM-DD HH:MM:SS.fff (up to 9 fractional digits)
     HH:MM:SS (today's date is assumed)
+    24:00:00 is the end of the day, :60 seconds are read as :59
     now (current date and time)
     today (current date at 00:00:00)
     yesterday (yesterday at 00:00:00)
     tomorrow (tomorrow at 00:00:00)
 
+Formats Tried With --guess, In Order:
+    YYYY-MM-DD, YYYY/MM/DD or YYYY.MM.DD, YYYYMMDD,
+    Mon DD YYYY ("Jan 5 2024", "January 5, 2024"), DD Mon YYYY,
+    MM/DD/YYYY, DD/MM/YYYY, DD.MM.YYYY, DD-MM-YYYY
+    Month names may be full or abbreviated. The time may follow as HH:MM or
+    HH:MM:SS, separated by a space or "T".
+
+Template Placeholders:
+    {years} {months} {days} {hours} {minutes} {seconds}
+                       Parts of the breakdown, as with --format
+    {total_seconds} {total_minutes} {total_hours} {total_days}
+                       Whole difference in one unit, rounded down
+    {d1} {d2}          The resolved dates (shown in --display-tz)
+    {{ and }} print literal braces
+
 Examples:
     datediff "2024-01-01" "2025-01-01"
     datediff -n "2024-01-01"
     datediff -u days "2024-01-01" "2024-02-01"
+    datediff -u days --round 0 "2024-01-01" "2024-02-12"
+    datediff --all-units "2024-01-01" now
     datediff -f "2024-01-01 12:00:00" "2024-01-02 15:30:45"
+    datediff -v --display-tz +09:00 "2024-01-01 12:00:00" now
+    datediff -b --holidays holidays.txt "2024-12-01" "2025-01-01"
+    datediff -t "{days}d {hours}h since {d1}" "2024-01-01 08:00:00" now
+    datediff --guess "Jan 5 2024" "2024/01/06"
+    datediff -p ms "2024-01-01 12:00:00.250" "2024-01-01 12:00:01.005"
+    datediff --humanize 90061
+    datediff --humanize "1d 2h 3m" -u minutes
+    datediff --span -u days 2024-03-14 2024-01-02 2024-06-30 2024-02-11
+
+Dates are interpreted and compared in UTC; --display-tz only changes how
+resolved dates are shown.
 "#;
 
 #[derive(Debug, Clone, Copy)]
@@ -39,6 +97,8 @@ struct DateTime {
     hour: u32,
     minute: u32,
     second: u32,
+    // Fraction of the second, from input like 12:00:00.250
+    nanosecond: u32,
 }
 
 impl DateTime {
@@ -50,10 +110,13 @@ impl DateTime {
             hour,
             minute,
             second,
+            nanosecond: 0,
         }
     }
 
-    fn from_str(s: &str) -> Result<Self, String> {
+    // Outside strict mode, 24:00:00 means midnight at the end of the day and
+    // a leap second (:60) is clamped to :59, as both appear in real logs
+    fn from_str(s: &str, strict: bool) -> Result<Self, String> {
         // Handle special keywords
         match s.to_lowercase().as_str() {
             "now" => return Ok(DateTime::now()),
@@ -78,20 +141,39 @@ impl DateTime {
         let day = date_parts[2].parse::<u32>()
             .map_err(|_| "Invalid day")?;
 
-        let (hour, minute, second) = if parts.len() > 1 {
+        let (hour, minute, second, nanosecond) = if parts.len() > 1 {
             let time_parts: Vec<&str> = parts[1].split(':').collect();
             if time_parts.len() != 3 {
                 return Err("Invalid time format. Expected HH:MM:SS".to_string());
             }
+            let (second, fraction) = match time_parts[2].split_once('.') {
+                Some((second, fraction)) => (second, Some(fraction)),
+                None => (time_parts[2], None),
+            };
             (
                 time_parts[0].parse::<u32>().map_err(|_| "Invalid hour")?,
                 time_parts[1].parse::<u32>().map_err(|_| "Invalid minute")?,
-                time_parts[2].parse::<u32>().map_err(|_| "Invalid second")?,
+                second.parse::<u32>().map_err(|_| "Invalid second")?,
+                match fraction {
+                    Some(fraction) => parse_fraction(fraction)?,
+                    None => 0,
+                },
             )
         } else {
-            (0, 0, 0)
+            (0, 0, 0, 0)
         };
 
+        let (mut hour, mut second) = (hour, second);
+        let mut next_day = false;
+        if !strict && hour == 24 && minute == 0 && second == 0 {
+            hour = 0;
+            next_day = true;
+        }
+        if !strict && second == 60 {
+            eprintln!("Note: leap second in '{}' treated as {:02}:{:02}:59", s, hour, minute);
+            second = 59;
+        }
+
         // Validate field ranges
         if month < 1 || month > 12 {
             return Err("Month must be between 1 and 12".to_string());
@@ -109,17 +191,30 @@ impl DateTime {
             return Err("Second must be between 0 and 59".to_string());
         }
 
-        Ok(DateTime::new(year, month, day, hour, minute, second))
+        if next_day && nanosecond > 0 {
+            return Err("Hour must be between 0 and 23".to_string());
+        }
+
+        if next_day {
+            let timestamp = date_to_seconds(year, month, day, 0, 0, 0) + 86400;
+            let (year, month, day, _, _, _) = seconds_to_date(timestamp);
+            return Ok(DateTime::new(year, month, day, 0, 0, 0));
+        }
+
+        let mut date = DateTime::new(year, month, day, hour, minute, second);
+        date.nanosecond = nanosecond;
+        Ok(date)
     }
 
     fn now() -> Self {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+            .unwrap();
 
-        let (year, month, day, hour, minute, second) = seconds_to_date(now as i64);
-        DateTime::new(year, month, day, hour, minute, second)
+        let (year, month, day, hour, minute, second) = seconds_to_date(now.as_secs() as i64);
+        let mut date = DateTime::new(year, month, day, hour, minute, second);
+        date.nanosecond = now.subsec_nanos();
+        date
     }
 
     fn today() -> Self {
@@ -145,6 +240,166 @@ impl DateTime {
         date_to_seconds(self.year, self.month, self.day, 
                        self.hour, self.minute, self.second)
     }
+
+    // Nanoseconds since the epoch; i128 so that any i32 year fits
+    fn nanos_since_epoch(&self) -> i128 {
+        self.to_seconds() as i128 * 1_000_000_000 + self.nanosecond as i128
+    }
+
+    // Format the date as seen from a UTC offset given in seconds
+    fn display(&self, offset: i64) -> String {
+        let (year, month, day, hour, minute, second) = seconds_to_date(self.to_seconds() + offset);
+        let fraction = if self.nanosecond > 0 {
+            format!(".{:09}", self.nanosecond).trim_end_matches('0').to_string()
+        } else {
+            String::new()
+        };
+        format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}{} {}",
+                year, month, day, hour, minute, second, fraction, format_offset(offset))
+    }
+}
+
+// The digits after the decimal point of the seconds, as nanoseconds
+fn parse_fraction(digits: &str) -> Result<u32, String> {
+    if digits.is_empty() || digits.len() > 9 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err("Invalid fractional seconds (1 to 9 digits expected)".to_string());
+    }
+    Ok(format!("{:0<9}", digits).parse().unwrap())
+}
+
+// Date layouts tried by --guess, in order of preference
+const GUESS_FORMATS: [&str; 9] = [
+    "YYYY-MM-DD", "YYYY/MM/DD", "YYYYMMDD", "Mon DD YYYY", "DD Mon YYYY",
+    "MM/DD/YYYY", "DD/MM/YYYY", "DD.MM.YYYY", "DD-MM-YYYY",
+];
+
+const MONTH_NAMES: [&str; 12] = [
+    "january", "february", "march", "april", "may", "june",
+    "july", "august", "september", "october", "november", "december",
+];
+
+// Month number for a full or abbreviated (at least three letters) name
+fn month_number(name: &str) -> Option<u32> {
+    let name = name.trim_end_matches('.').to_lowercase();
+    if name.len() < 3 {
+        return None;
+    }
+    MONTH_NAMES.iter()
+        .position(|month| month.starts_with(&name))
+        .map(|index| index as u32 + 1)
+}
+
+// Year, month and day from the date parts of the input if they fit `format`;
+// `separator` is what split them, or None for a single compact part
+fn guess_date(format: &str, parts: &[&str], separator: Option<char>) -> Option<(i32, u32, u32)> {
+    let year = |part: &str| {
+        if part.len() == 4 { part.parse::<i32>().ok() } else { None }
+    };
+    // Ordinal suffixes as in "5th" are allowed on days
+    let day = |part: &str| {
+        part.trim_end_matches(|c: char| c.is_ascii_alphabetic()).parse::<u32>().ok()
+    };
+    let number = |part: &str| part.parse::<u32>().ok();
+
+    match (format, parts, separator) {
+        ("YYYY-MM-DD", &[y, m, d], Some('-')) |
+        ("YYYY/MM/DD", &[y, m, d], Some('/')) |
+        ("YYYY/MM/DD", &[y, m, d], Some('.')) => Some((year(y)?, number(m)?, number(d)?)),
+        ("YYYYMMDD", &[compact], None) if compact.len() == 8 && compact.bytes().all(|b| b.is_ascii_digit()) => {
+            Some((year(&compact[..4])?, number(&compact[4..6])?, number(&compact[6..])?))
+        }
+        ("Mon DD YYYY", &[m, d, y], _) => Some((year(y)?, month_number(m)?, day(d)?)),
+        ("DD Mon YYYY", &[d, m, y], _) => Some((year(y)?, month_number(m)?, day(d)?)),
+        ("MM/DD/YYYY", &[m, d, y], Some('/')) => Some((year(y)?, number(m)?, number(d)?)),
+        ("DD/MM/YYYY", &[d, m, y], Some('/')) |
+        ("DD.MM.YYYY", &[d, m, y], Some('.')) |
+        ("DD-MM-YYYY", &[d, m, y], Some('-')) => Some((year(y)?, number(m)?, number(d)?)),
+        _ => None,
+    }
+}
+
+// Parse a date in whichever of GUESS_FORMATS fits first, after the formats
+// DateTime::from_str understands
+fn guess_parse(s: &str, strict: bool) -> Result<DateTime, String> {
+    if let Ok(date) = DateTime::from_str(s, strict) {
+        return Ok(date);
+    }
+
+    // Split off the time, given after a space or an ISO "T"
+    let mut date_tokens = Vec::new();
+    let mut time = "00:00:00".to_string();
+    for token in s.split(|c: char| c.is_whitespace() || c == ',').filter(|t| !t.is_empty()) {
+        let (date_part, time_part) = match token.find('T') {
+            Some(pos) if token.contains(':') => (&token[..pos], &token[pos + 1..]),
+            _ if token.contains(':') => ("", token),
+            _ => (token, ""),
+        };
+        if !date_part.is_empty() {
+            date_tokens.push(date_part);
+        }
+        if !time_part.is_empty() {
+            time = if time_part.matches(':').count() == 1 {
+                format!("{}:00", time_part)
+            } else {
+                time_part.to_string()
+            };
+        }
+    }
+
+    let (parts, separator) = if date_tokens.len() == 1 {
+        let token = date_tokens[0];
+        match token.chars().find(|c| ['-', '/', '.'].contains(c)) {
+            Some(separator) => (token.split(separator).collect(), Some(separator)),
+            None => (date_tokens, None),
+        }
+    } else {
+        (date_tokens, Some(' '))
+    };
+
+    for format in GUESS_FORMATS.iter() {
+        if let Some((year, month, day)) = guess_date(format, &parts, separator) {
+            let normalized = format!("{:04}-{:02}-{:02} {}", year, month, day, time);
+            if let Ok(date) = DateTime::from_str(&normalized, strict) {
+                return Ok(date);
+            }
+        }
+    }
+
+    Err(format!("Unrecognized date '{}' (tried {})", s, GUESS_FORMATS.join(", ")))
+}
+
+// Parse a UTC offset such as "+09:00", "-0530", "+9", "Z" or "UTC" into seconds
+fn parse_offset(s: &str) -> Result<i64, String> {
+    if s.eq_ignore_ascii_case("z") || s.eq_ignore_ascii_case("utc") {
+        return Ok(0);
+    }
+
+    let invalid = || format!("Invalid timezone offset: {}", s);
+    let sign = match s.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return Err(invalid()),
+    };
+
+    let digits = s[1..].replace(':', "");
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.as_str(), "0"),
+        4 => digits.split_at(2),
+        _ => return Err(invalid()),
+    };
+    let hours: i64 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i64 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 14 || minutes > 59 {
+        return Err(invalid());
+    }
+
+    Ok(sign * (hours * 3600 + minutes * 60))
+}
+
+fn format_offset(offset: i64) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.abs();
+    format!("{}{:02}:{:02}", sign, offset / 3600, (offset % 3600) / 60)
 }
 
 // Convert a date to seconds since the Unix epoch
@@ -152,40 +407,46 @@ fn date_to_seconds(year: i32, month: u32, day: u32,
                   hour: u32, minute: u32, second: u32) -> i64 {
     let days_before_month = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
     
-    let mut years = year - 1970;
-    let mut days = days_before_month[month as usize - 1] + day - 1;
-    
-    // Add extra days for leap years
-    let leap_years = (1968 + years) / 4 - (1968 + years) / 100 + (1968 + years) / 400 
-                    - (1968) / 4 + (1968) / 100 - (1968) / 400;
-    days += leap_years as u32;
+    // Leap days in years 1..=n
+    let leaps = |n: i64| n.div_euclid(4) - n.div_euclid(100) + n.div_euclid(400);
+
+    let year = year as i64;
+    let mut days = (year - 1970) * 365 + leaps(year - 1) - leaps(1969)
+        + days_before_month[month as usize - 1] + day as i64 - 1;
     
     // Check if the current year is a leap year
     if month > 2 && ((year % 4 == 0 && year % 100 != 0) || year % 400 == 0) {
         days += 1;
     }
     
-    days as i64 * 86400 + hour as i64 * 3600 + minute as i64 * 60 + second as i64
+    days * 86400 + hour as i64 * 3600 + minute as i64 * 60 + second as i64
 }
 
 // Convert seconds since the Unix epoch to a date
 fn seconds_to_date(secs: i64) -> (i32, u32, u32, u32, u32, u32) {
-    let days = secs / 86400;
-    let secs_of_day = secs % 86400;
+    // Rounded down, so times before the epoch still get a positive time of day
+    let days = secs.div_euclid(86400);
+    let secs_of_day = secs.rem_euclid(86400);
     
     let hour = (secs_of_day / 3600) as u32;
     let minute = ((secs_of_day % 3600) / 60) as u32;
     let second = (secs_of_day % 60) as u32;
     
+    let days_in_year = |year: i32| {
+        if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 { 366 } else { 365 }
+    };
+
     let mut year = 1970;
     let mut days_remaining = days;
     
+    // Before the epoch, walk back to the start of the year the day falls in
+    while days_remaining < 0 {
+        year -= 1;
+        days_remaining += days_in_year(year);
+    }
+
     while days_remaining >= 365 {
-        let days_in_year = if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 {
-            366
-        } else {
-            365
-        };
+        let days_in_year = days_in_year(year);
         
         if days_remaining >= days_in_year {
             days_remaining -= days_in_year;
@@ -227,13 +488,27 @@ struct TimeDiff {
     hours: i64,
     minutes: i64,
     seconds: i64,
+    // Below the whole seconds, in nanoseconds
+    nanoseconds: i64,
     total_seconds: i64,
+    total_nanos: i128,
 }
 
 fn calculate_diff(date1: DateTime, date2: DateTime) -> TimeDiff {
-    let seconds1 = date1.to_seconds();
-    let seconds2 = date2.to_seconds();
-    let total_seconds = seconds2 - seconds1;
+    diff_from_nanos(date2.nanos_since_epoch() - date1.nanos_since_epoch())
+}
+
+// Indexes of the earliest and latest dates for --span, down to the
+// nanosecond; ties go to the input given first
+fn span_bounds(dates: &[DateTime]) -> (usize, usize) {
+    let first = (0..dates.len()).min_by_key(|&i| dates[i].nanos_since_epoch()).unwrap_or(0);
+    let last = (0..dates.len()).rev().max_by_key(|&i| dates[i].nanos_since_epoch()).unwrap_or(0);
+    (first, last)
+}
+
+// Break a span down the same way whether it came from two dates or --humanize
+fn diff_from_nanos(total_nanos: i128) -> TimeDiff {
+    let total_seconds = (total_nanos / 1_000_000_000) as i64;
     
     let total_days = total_seconds / 86400;
     let years = total_days / 365;
@@ -253,11 +528,101 @@ fn calculate_diff(date1: DateTime, date2: DateTime) -> TimeDiff {
         hours,
         minutes,
         seconds,
+        nanoseconds: (total_nanos % 1_000_000_000) as i64,
         total_seconds,
+        total_nanos,
+    }
+}
+
+// A --humanize duration: plain seconds, or numbers each followed by a unit,
+// with or without spaces in between. Years and months are 365 and 30 days.
+fn parse_duration(spec: &str) -> Result<i64, String> {
+    let spec = spec.trim();
+    if let Ok(seconds) = spec.parse::<i64>() {
+        return Ok(seconds);
+    }
+
+    let invalid = || format!("Invalid duration '{}' (expected e.g. 90061 or \"1d 2h 3m\")", spec);
+    if spec.is_empty() {
+        return Err(invalid());
     }
+    let mut total: i64 = 0;
+    let mut rest = spec;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(invalid());
+        }
+        let amount: i64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = rest[digits..].trim_start();
+        let letters = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        let unit = match rest[..letters].to_lowercase().as_str() {
+            "y" | "year" | "years" => 365 * 86400,
+            "mo" | "month" | "months" => 30 * 86400,
+            "w" | "week" | "weeks" => 7 * 86400,
+            "d" | "day" | "days" => 86400,
+            "h" | "hour" | "hours" => 3600,
+            "m" | "min" | "minute" | "minutes" => 60,
+            "s" | "sec" | "second" | "seconds" => 1,
+            _ => return Err(invalid()),
+        };
+        total = amount.checked_mul(unit)
+            .and_then(|seconds| total.checked_add(seconds))
+            .ok_or_else(|| format!("Duration '{}' is too long", spec))?;
+        rest = rest[letters..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    }
+    Ok(total)
 }
 
-fn format_diff(diff: &TimeDiff, unit: Option<&str>, format: bool, simple: bool) -> String {
+// Name of the smallest unit for --precision, by its number of fractional digits
+fn subsecond_unit(digits: usize) -> &'static str {
+    match digits {
+        3 => "milliseconds",
+        6 => "microseconds",
+        _ => "nanoseconds",
+    }
+}
+
+// The whole difference in seconds, with `digits` fractional digits (truncated)
+fn seconds_text(diff: &TimeDiff, digits: usize) -> String {
+    if digits == 0 {
+        return diff.total_seconds.to_string();
+    }
+    let sign = if diff.total_nanos < 0 { "-" } else { "" };
+    let nanos = diff.total_nanos.unsigned_abs();
+    format!("{}{}.{:0width$}", sign, nanos / 1_000_000_000,
+            (nanos % 1_000_000_000) / 10u128.pow(9 - digits as u32), width = digits)
+}
+
+// Units for --all-units with their length in seconds; years and months
+// are 365 and 30 days, as with --unit
+const ALL_UNITS: [(&str, i64); 7] = [
+    ("years", 365 * 86400), ("months", 30 * 86400), ("weeks", 7 * 86400),
+    ("days", 86400), ("hours", 3600), ("minutes", 60), ("seconds", 1),
+];
+
+// `subsec_digits` is 0 for whole seconds, or 3, 6 or 9 with --precision
+fn format_diff(diff: &TimeDiff, unit: Option<&str>, format: bool, all_units: bool,
+               simple: bool, precision: usize, subsec_digits: usize) -> String {
+    // Fractional units only take the sub-second part into account when asked to
+    let seconds = if subsec_digits > 0 {
+        diff.total_nanos as f64 / 1e9
+    } else {
+        diff.total_seconds as f64
+    };
+
+    if all_units {
+        let lines: Vec<String> = ALL_UNITS.iter()
+            .map(|&(name, divisor)| match (simple, divisor) {
+                (true, 1) => seconds_text(diff, subsec_digits),
+                (true, _) => format!("{}", diff.total_seconds / divisor),
+                (false, 1) => format!("{} {}", seconds_text(diff, subsec_digits), name),
+                (false, _) => format!("{:.*} {}", precision, seconds / divisor as f64, name),
+            })
+            .collect();
+        return lines.join("\n");
+    }
+
     if simple {
         if let Some(unit) = unit {
             match unit {
@@ -266,11 +631,11 @@ fn format_diff(diff: &TimeDiff, unit: Option<&str>, format: bool, simple: bool)
                 "days" => return format!("{}", diff.total_seconds / 86400),
                 "hours" => return format!("{}", diff.total_seconds / 3600),
                 "minutes" => return format!("{}", diff.total_seconds / 60),
-                "seconds" => return format!("{}", diff.total_seconds),
+                "seconds" => return seconds_text(diff, subsec_digits),
                 _ => {}
             }
         }
-        return diff.total_seconds.to_string();
+        return seconds_text(diff, subsec_digits);
     }
 
     if format {
@@ -293,6 +658,10 @@ fn format_diff(diff: &TimeDiff, unit: Option<&str>, format: bool, simple: bool)
         if diff.seconds > 0 {
             parts.push(format!("{} seconds", diff.seconds));
         }
+        let subsecond = diff.nanoseconds / 10i64.pow(9 - subsec_digits as u32);
+        if subsec_digits > 0 && subsecond > 0 {
+            parts.push(format!("{} {}", subsecond, subsecond_unit(subsec_digits)));
+        }
         
         if parts.is_empty() {
             return "0 seconds".to_string();
@@ -302,19 +671,108 @@ fn format_diff(diff: &TimeDiff, unit: Option<&str>, format: bool, simple: bool)
 
     if let Some(unit) = unit {
         match unit {
-            "years" => format!("{:.2} years", diff.total_seconds as f64 / (365.0 * 86400.0)),
-            "months" => format!("{:.2} months", diff.total_seconds as f64 / (30.0 * 86400.0)),
-            "days" => format!("{:.2} days", diff.total_seconds as f64 / 86400.0),
-            "hours" => format!("{:.2} hours", diff.total_seconds as f64 / 3600.0),
-            "minutes" => format!("{:.2} minutes", diff.total_seconds as f64 / 60.0),
-            "seconds" => format!("{} seconds", diff.total_seconds),
+            "years" => format!("{:.*} years", precision, seconds / (365.0 * 86400.0)),
+            "months" => format!("{:.*} months", precision, seconds / (30.0 * 86400.0)),
+            "days" => format!("{:.*} days", precision, seconds / 86400.0),
+            "hours" => format!("{:.*} hours", precision, seconds / 3600.0),
+            "minutes" => format!("{:.*} minutes", precision, seconds / 60.0),
+            "seconds" => format!("{} seconds", seconds_text(diff, subsec_digits)),
             _ => format!("Invalid unit: {}", unit),
         }
     } else {
-        format!("{:.2} days", diff.total_seconds as f64 / 86400.0)
+        format!("{:.*} days", precision, seconds / 86400.0)
     }
 }
 
+// Fill in a --template. Placeholders are {name}; {{ and }} are literal braces.
+fn render_template(template: &str, diff: &TimeDiff, d1: &str, d2: &str) -> Result<String, String> {
+    let mut output = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                output.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                output.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("Unclosed placeholder {{{} in template", name)),
+                    }
+                }
+                let value = match name.as_str() {
+                    "years" => diff.years.to_string(),
+                    "months" => diff.months.to_string(),
+                    "days" => diff.days.to_string(),
+                    "hours" => diff.hours.to_string(),
+                    "minutes" => diff.minutes.to_string(),
+                    "seconds" => diff.seconds.to_string(),
+                    "total_seconds" => diff.total_seconds.to_string(),
+                    "total_minutes" => (diff.total_seconds / 60).to_string(),
+                    "total_hours" => (diff.total_seconds / 3600).to_string(),
+                    "total_days" => (diff.total_seconds / 86400).to_string(),
+                    "d1" => d1.to_string(),
+                    "d2" => d2.to_string(),
+                    _ => return Err(format!("Unknown placeholder {{{}}} in template", name)),
+                };
+                output.push_str(&value);
+            }
+            '}' => return Err("Unmatched '}' in template (use }} for a literal brace)".to_string()),
+            c => output.push(c),
+        }
+    }
+    Ok(output)
+}
+
+// Days since 1970-01-01, the epoch being a Thursday
+fn day_number(date: &DateTime) -> i64 {
+    date.to_seconds().div_euclid(86400)
+}
+
+fn is_weekday(day: i64) -> bool {
+    // 0 is Monday
+    (day + 3).rem_euclid(7) < 5
+}
+
+// Read one YYYY-MM-DD date per line; blank lines and '#' comments are ignored
+fn read_holidays(path: &str) -> Result<HashSet<i64>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read holidays file {}: {}", path, e))?;
+
+    let mut holidays = HashSet::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match DateTime::from_str(line, false) {
+            Ok(date) => {
+                holidays.insert(day_number(&date));
+            }
+            Err(e) => eprintln!("Warning: {}:{}: skipping '{}': {}", path, number + 1, line, e),
+        }
+    }
+    Ok(holidays)
+}
+
+// Working days from the day of date1 up to the day of date2, negative when
+// date2 comes first
+fn business_days(date1: &DateTime, date2: &DateTime, holidays: &HashSet<i64>) -> i64 {
+    let (start, end) = (day_number(date1), day_number(date2));
+    let (from, to, sign) = if start <= end { (start, end, 1) } else { (end, start, -1) };
+    let count = (from..to)
+        .filter(|&day| is_weekday(day) && !holidays.contains(&day))
+        .count() as i64;
+    count * sign
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     let mut date1_str = String::new();
@@ -323,6 +781,20 @@ fn main() {
     let mut unit = None;
     let mut format = false;
     let mut simple = false;
+    let mut precision = 2;
+    let mut subsec_digits = 0;
+    let mut verbose = false;
+    let mut display_offset = 0;
+    let mut business = false;
+    let mut strict = false;
+    let mut holidays_path = None;
+    let mut span = false;
+    let mut template = None;
+    let mut guess = false;
+    let mut all_units = false;
+    let mut humanize = None;
+    // Dates past the second one, only used with --span
+    let mut more_dates = Vec::new();
     
     let mut i = 1;
     while i < args.len() {
@@ -344,6 +816,10 @@ fn main() {
                     process::exit(1);
                 }
             }
+            "-a" | "--all-units" => {
+                all_units = true;
+                i += 1;
+            }
             "-f" | "--format" => {
                 format = true;
                 i += 1;
@@ -352,46 +828,304 @@ fn main() {
                 simple = true;
                 i += 1;
             }
+            "-r" | "--round" => {
+                if i + 1 < args.len() {
+                    precision = match args[i + 1].parse::<usize>() {
+                        Ok(n) => n,
+                        Err(_) => {
+                            eprintln!("Error: Invalid rounding value: {}", args[i + 1]);
+                            process::exit(1);
+                        }
+                    };
+                    i += 2;
+                } else {
+                    eprintln!("Error: Rounding precision not specified");
+                    process::exit(1);
+                }
+            }
+            "-p" | "--precision" => {
+                if i + 1 < args.len() {
+                    subsec_digits = match args[i + 1].as_str() {
+                        "s" => 0,
+                        "ms" => 3,
+                        "us" => 6,
+                        "ns" => 9,
+                        other => {
+                            eprintln!("Error: Invalid precision: {} (expected s, ms, us or ns)", other);
+                            process::exit(1);
+                        }
+                    };
+                    i += 2;
+                } else {
+                    eprintln!("Error: Precision not specified");
+                    process::exit(1);
+                }
+            }
+            "-v" | "--verbose" => {
+                verbose = true;
+                i += 1;
+            }
+            "--display-tz" => {
+                if i + 1 < args.len() {
+                    display_offset = match parse_offset(&args[i + 1]) {
+                        Ok(offset) => offset,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    i += 2;
+                } else {
+                    eprintln!("Error: Timezone offset not specified");
+                    process::exit(1);
+                }
+            }
+            "--strict" => {
+                strict = true;
+                i += 1;
+            }
+            "-b" | "--business-days" => {
+                business = true;
+                i += 1;
+            }
+            "--holidays" => {
+                if i + 1 < args.len() {
+                    holidays_path = Some(args[i + 1].clone());
+                    business = true;
+                    i += 2;
+                } else {
+                    eprintln!("Error: Holidays file not specified");
+                    process::exit(1);
+                }
+            }
+            "-t" | "--template" | "--format-string" => {
+                if i + 1 < args.len() {
+                    template = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("Error: Template not specified");
+                    process::exit(1);
+                }
+            }
+            "--span" => {
+                span = true;
+                i += 1;
+            }
+            "--humanize" | "--duration" => {
+                if i + 1 < args.len() {
+                    humanize = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("Error: Duration not specified");
+                    process::exit(1);
+                }
+            }
+            "-g" | "--guess" => {
+                guess = true;
+                i += 1;
+            }
             _ => {
                 if date1_str.is_empty() {
                     date1_str = args[i].clone();
                 } else if date2_str.is_empty() {
                     date2_str = args[i].clone();
+                } else {
+                    more_dates.push(args[i].clone());
                 }
                 i += 1;
             }
         }
     }
 
+    if all_units && (unit.is_some() || format) {
+        eprintln!("Error: --all-units cannot be combined with --unit or --format");
+        process::exit(1);
+    }
+
+    // No dates involved: the duration is broken down directly
+    if let Some(ref spec) = humanize {
+        if !date1_str.is_empty() || use_now || span || business || template.is_some() {
+            eprintln!("Error: --humanize takes a duration instead of dates and cannot be \
+                       combined with --now, --span, --business-days or --template");
+            process::exit(1);
+        }
+        let seconds = match parse_duration(spec) {
+            Ok(seconds) => seconds,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        };
+        let diff = diff_from_nanos(seconds as i128 * 1_000_000_000);
+        let format = format || (unit.is_none() && !all_units && !simple);
+        println!("{}", format_diff(&diff, unit, format, all_units, simple, precision, subsec_digits));
+        return;
+    }
+
     if date1_str.is_empty() {
         eprintln!("Error: First date not specified");
         eprintln!("Try 'datediff --help' for more information.");
         process::exit(1);
     }
 
-    if use_now {
-        date2_str = "now".to_string();
-    }
+    let parse = |s: &str| if guess { guess_parse(s, strict) } else { DateTime::from_str(s, strict) };
 
-    if date2_str.is_empty() {
+    if use_now && !span {
         date2_str = "now".to_string();
     }
 
-    let date1 = match DateTime::from_str(&date1_str) {
-        Ok(date) => date,
-        Err(e) => {
-            eprintln!("Error parsing first date: {}", e);
+    let (date1, date2) = if span {
+        let mut inputs = vec![date1_str];
+        if !date2_str.is_empty() {
+            inputs.push(date2_str);
+        }
+        inputs.extend(more_dates);
+        if use_now {
+            inputs.push("now".to_string());
+        }
+        if inputs.len() < 2 {
+            eprintln!("Error: --span needs at least two dates");
             process::exit(1);
         }
-    };
 
-    let date2 = match DateTime::from_str(&date2_str) {
-        Ok(date) => date,
-        Err(e) => {
-            eprintln!("Error parsing second date: {}", e);
-            process::exit(1);
+        let mut dates = Vec::new();
+        for (index, input) in inputs.iter().enumerate() {
+            match parse(input) {
+                Ok(date) => dates.push(date),
+                Err(e) => {
+                    eprintln!("Error parsing date {} ({}): {}", index + 1, input, e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        let (first, last) = span_bounds(&dates);
+        if !simple {
+            println!("Earliest: {} (date {}: {})",
+                     dates[first].display(display_offset), first + 1, inputs[first]);
+            println!("Latest:   {} (date {}: {})",
+                     dates[last].display(display_offset), last + 1, inputs[last]);
+        }
+        // The resolved dates were just shown
+        verbose = false;
+        (dates[first], dates[last])
+    } else {
+        if date2_str.is_empty() {
+            date2_str = "now".to_string();
         }
+
+        let date1 = match parse(&date1_str) {
+            Ok(date) => date,
+            Err(e) => {
+                eprintln!("Error parsing first date: {}", e);
+                process::exit(1);
+            }
+        };
+
+        let date2 = match parse(&date2_str) {
+            Ok(date) => date,
+            Err(e) => {
+                eprintln!("Error parsing second date: {}", e);
+                process::exit(1);
+            }
+        };
+        (date1, date2)
     };
+    if verbose && !simple {
+        println!("From: {}", date1.display(display_offset));
+        println!("To:   {}", date2.display(display_offset));
+    }
+
+    if business {
+        let holidays = match holidays_path {
+            Some(ref path) => match read_holidays(path) {
+                Ok(holidays) => holidays,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            },
+            None => HashSet::new(),
+        };
+        let days = business_days(&date1, &date2, &holidays);
+        if simple {
+            println!("{}", days);
+        } else {
+            println!("{} business days", days);
+        }
+        return;
+    }
+
     let diff = calculate_diff(date1, date2);
-        println!("{}", format_diff(&diff, unit, format, simple));
-}
\ No newline at end of file
+    if let Some(ref template) = template {
+        let d1 = date1.display(display_offset);
+        let d2 = date2.display(display_offset);
+        match render_template(template, &diff, &d1, &d2) {
+            Ok(text) => println!("{}", text),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+        println!("{}", format_diff(&diff, unit, format, all_units, simple, precision, subsec_digits));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seconds_to_date_round_trips_around_the_epoch() {
+        let dates = [
+            (1969, 12, 31, 23, 59, 59), (1970, 1, 1, 0, 0, 0), (1960, 1, 1, 0, 0, 0),
+            (1960, 2, 29, 12, 30, 15), (1900, 3, 1, 6, 0, 0), (1600, 12, 31, 0, 0, 1),
+            (2024, 2, 29, 23, 0, 0),
+        ];
+        for &(year, month, day, hour, minute, second) in &dates {
+            let secs = date_to_seconds(year, month, day, hour, minute, second);
+            assert_eq!(seconds_to_date(secs), (year, month, day, hour, minute, second));
+        }
+        assert_eq!(seconds_to_date(-1), (1969, 12, 31, 23, 59, 59));
+    }
+
+    #[test]
+    fn display_before_the_epoch() {
+        let date = DateTime::from_str("1960-01-01", false).unwrap();
+        assert_eq!(date.display(0), "1960-01-01 00:00:00 +00:00");
+        assert_eq!(date.display(-3600), "1959-12-31 23:00:00 -01:00");
+    }
+
+    #[test]
+    fn end_of_day_rolls_over_before_the_epoch() {
+        let date = DateTime::from_str("1960-01-01 24:00:00", false).unwrap();
+        assert_eq!((date.year, date.month, date.day, date.hour), (1960, 1, 2, 0));
+        let date = DateTime::from_str("1959-12-31 24:00:00", false).unwrap();
+        assert_eq!((date.year, date.month, date.day), (1960, 1, 1));
+
+        let end = DateTime::from_str("1960-01-03", false).unwrap();
+        let diff = calculate_diff(DateTime::from_str("1960-01-01 24:00:00", false).unwrap(), end);
+        assert_eq!(diff.total_seconds, 86400);
+    }
+
+    #[test]
+    fn guess_parse_rejects_compact_dates_that_are_not_digits() {
+        let date = guess_parse("20240131", false).unwrap();
+        assert_eq!((date.year, date.month, date.day), (2024, 1, 31));
+        // Eight bytes, but not eight digits
+        for input in ["abcédef", "2024é01", "abcdefgh", "2024-101", "+2024013"] {
+            assert!(guess_parse(input, false).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn span_orders_dates_within_the_same_second() {
+        let dates: Vec<DateTime> = ["2024-01-01 12:00:00.5", "2024-01-01 12:00:00.25",
+                                    "2024-01-01 12:00:00.75", "2024-01-01 12:00:00.25"]
+            .iter()
+            .map(|s| DateTime::from_str(s, false).unwrap())
+            .collect();
+        assert_eq!(span_bounds(&dates), (1, 2));
+    }
+}
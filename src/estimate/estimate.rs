@@ -1,38 +1,359 @@
 use std::env;
-use std::process::{Command, Stdio};
-use std::time::{Duration, Instant};
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::collections::VecDeque;
-use std::io::{self, Write};
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::ExitStatusExt;
+use std::thread;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
 const HELP: &str = r#"
 Estimate - Command execution time estimation tool
 
 Usage: 
     estimate [OPTIONS] <command> [args...]
+    estimate [OPTIONS] -- <command> [args...]
 
 Options:
     -n, --iterations <N>    Number of iterations for averaging (default: 3)
     -w, --warmup <N>        Number of warmup runs (default: 1)
+    --auto-warmup           Warm up until consecutive runs differ by less than
+                            the warmup threshold (ignored when -w is given)
+    --warmup-threshold <P>  Relative change in percent for --auto-warmup (default: 5)
+    --max-warmup <N>        Upper bound on adaptive warmup runs (default: 10)
+    --min-time <TIME>       Run until measured time reaches TIME (e.g. 5s, 500ms)
+    --max-iterations <N>    Upper bound on runs with --min-time (default: 1000)
+    --max-time <TIME>       Stop starting new runs once TIME has passed in total,
+                            warmup included; exits with status 3 if even the
+                            warmup did not finish. Overrides --min-time
+    --noise-threshold <P>   Warn when the standard deviation exceeds P% of the
+                            mean (default: 10)
+    --trim-outliers         Exclude outlier runs from the statistics
+    --outlier-threshold <K> Outlier cutoff in median absolute deviations (default: 3)
     -q, --quiet            Quiet mode - only show final results
+    -v, --verbose          Show the whole stderr of every failed run, not just
+                           the last lines of the first
     -s, --simple           Simple output format
+    --markdown             Print results as a Markdown table (nothing else on stdout)
+    --field <F>            Print only one value: min, max, avg, median, p95,
+                           stddev, total or cpu in nanoseconds, success or fail
+                           counts, bytes_per_sec (needs --bytes) or
+                           items_per_sec (needs --items)
+    --color <WHEN>         Colorize output: auto, always or never (default: auto)
+    --histogram [N]        Show a histogram of run durations with N buckets (default: 10)
+    --bytes <N|auto:FILE>  Bytes processed per run, or the size of FILE; adds
+                           throughput (per second) to the results
+    --items <N>            Items processed per run; adds items per second
+    --window <N>           Report mean/min/max of the last N runs every N runs
+                           to reveal drift such as thermal throttling
+    --success-exit-codes <C,..>
+                           Exit codes that count as success (default: 0)
+    --ignore-exit-code     Count every completed run as a success
+    --retries <N>          Rerun a failed run up to N times; only the last
+                           attempt is recorded
+    --prepare <CMD>        Run CMD through the shell before every run (not timed)
+    --cleanup <CMD>        Run CMD through the shell after every run (not timed)
+    --cwd <DIR>            Run the command and hooks in DIR
+    --stdin-file <FILE>    Feed FILE to the command's stdin, reopened for every
+                           run (default: stdin is inherited)
+    --env <KEY=VALUE>      Set an environment variable for every run, warmup and
+                           measured alike, and for the hooks (repeatable)
+    --env-clear            Start runs from an empty environment
+    --show-output          Show the command's stdout/stderr (disables progress)
+    --output-to <DIR>      Save each run's stdout/stderr to numbered files in DIR
+    --keep-output <DIR>    Like --output-to, but only for measured runs
+    --keep-warmup-output   Also keep the output of warmup runs
+    --force                Overwrite output files left in DIR by an earlier run
+    --check-output         Warn if stdout differs between runs (compared by
+                           hash after timing; keep the outputs with --output-to)
+    -c, --command <CMD>    Benchmark CMD through the shell; repeat to compare
+                           several commands and rank them
+    --label <NAME>         Name the preceding -c command in the output
+                           (default: cmd1, cmd2, ...)
+    --suite <FILE>         Benchmark every command listed in FILE and rank them
+    --param <NAME>=<V,..>  Substitute each value for {NAME} in the command and
+                           benchmark every combination (repeatable)
+    -j, --jobs <N,..>      Start N copies of the command at once in every run
+                           and time the whole wave as well as each copy; give
+                           several counts (e.g. 1,4) to compare contention
+    --interleave           Alternate measured runs between commands (default)
+    --sequential           Finish all runs of one command before the next
+    --randomize [SEED]     Shuffle the order of commands in every round of
+                           interleaved runs; give SEED to reproduce an order
+    --export-json <FILE>   Write every run of every command to FILE as JSON,
+                           whatever is shown on the terminal
+    --save-baseline <FILE> Save the results as a JSON baseline
+    --baseline <FILE>      Compare the results against a saved baseline; if FILE
+                           does not exist yet, save the results there instead
+    --max-regression <P>   Exit with status 2 if slower than the baseline by more than P%
+    --threshold <P>        Same as --max-regression
+    --compare-metric <M>   Metric for baseline comparison: mean, median or p95 (default: mean)
     -h, --help             Show this help message
 
 Example:
     estimate -n 5 ls -la
     estimate -w 2 -n 3 find . -type f
     estimate -s "sleep 1"
+    estimate --min-time 5s ./a.out
+    estimate --max-time 2m -n 100 ./slow-test.sh
+    estimate -n 200 --window 20 ./a.out
+    estimate --bytes auto:dump.sql -n 5 gzip -k -f dump.sql
+    estimate --stdin-file words.txt --bytes auto:words.txt sort
+    estimate --output-to runs/ ./build.sh
+    estimate --keep-output runs/ --force -n 5 ./build.sh
+    estimate --suite benchmarks.txt
+    estimate -c "./parse-v1 big.json" --label baseline -c "./parse-v2 big.json" --label new-parser
+    estimate --param N=1,2,4,8 -n 5 ./myprog --threads {N}
+    estimate -j 1,4 -n 5 make test
+    estimate --prepare "rm -rf target" cargo build
+    estimate --success-exit-codes 0,1 grep -r TODO src
+    estimate --baseline base.json --max-regression 10 ./a.out
+    SLOW=$(estimate -n 10 --field median -- ./prog)
+    estimate --export-json runs.json -n 20 ./a.out
 
 Note: Use quotes for commands with arguments
+
+Suite files contain one command per line, run through 'sh -c'.
+Empty lines and lines starting with '#' are ignored.
 "#;
 
+// ANSI styling lives here so it can be switched off as a whole
+mod style {
+    pub const BOLD: &str = "\x1b[1m";
+    pub const RED: &str = "\x1b[31m";
+    pub const GREEN: &str = "\x1b[32m";
+    pub const YELLOW: &str = "\x1b[33m";
+    const RESET: &str = "\x1b[0m";
+
+    pub fn paint(text: &str, code: &str, enabled: bool) -> String {
+        if enabled {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+// Default coefficient of variation above which timings are flagged as noisy
+const HIGH_VARIANCE: f64 = 0.10;
+
+#[derive(Debug)]
+enum OutputMode {
+    Discard,
+    Inherit,
+    Directory(PathBuf),
+}
+
+// Lower bound on measured runs in --min-time mode
+const MIN_TIMED_RUNS: usize = 3;
+
+// Fewer samples than this make the median absolute deviation meaningless
+const MIN_OUTLIER_SAMPLES: usize = 5;
+
+// Two-sided 95% critical values of Student's t for 1..=30 degrees of freedom
+const T_TABLE_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+    2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+];
+
 #[derive(Debug)]
 struct Config {
     iterations: usize,
     warmup: usize,
+    auto_warmup: bool,
+    warmup_threshold: f64,
+    max_warmup: usize,
+    min_time: Option<Duration>,
+    max_iterations: usize,
+    max_time: Option<Duration>,
+    // When the --max-time budget started counting
+    started: Instant,
+    trim_outliers: bool,
+    outlier_threshold: f64,
+    noise_threshold: f64,
+    // 1-minute load average before the first run, where /proc/loadavg exists
+    load_average: Option<f64>,
     quiet: bool,
+    verbose: bool,
     simple: bool,
+    markdown: bool,
+    field: Option<Field>,
+    color: bool,
+    histogram: Option<usize>,
+    window: Option<usize>,
+    // Work done by one run, for throughput
+    bytes: Option<u64>,
+    items: Option<u64>,
+    output: OutputMode,
+    // Whether a Directory output mode also saves warmup runs
+    keep_warmup_output: bool,
+    force: bool,
+    prepare: Option<String>,
+    cleanup: Option<String>,
+    cwd: Option<PathBuf>,
+    stdin_file: Option<PathBuf>,
+    env: Vec<(String, String)>,
+    env_clear: bool,
+    suite: Option<PathBuf>,
+    sequential: bool,
+    seed: Option<u64>,
+    params: Vec<(String, Vec<String>)>,
+    // Copies started at once per run, one benchmark per count
+    concurrency: Vec<usize>,
+    export_json: Option<PathBuf>,
+    save_baseline: Option<PathBuf>,
+    baseline: Option<PathBuf>,
+    max_regression: Option<f64>,
+    compare_metric: Metric,
+    check_output: bool,
+    success_codes: Vec<i32>,
+    ignore_exit_code: bool,
+    retries: usize,
+    // Commands given with -c, each with its label
+    commands: Vec<(String, String)>,
+    command: String,
+    args: Vec<String>,
+}
+
+// Statistic used when comparing against a baseline
+#[derive(Debug, Clone, Copy)]
+enum Metric {
+    Mean,
+    Median,
+    P95,
+}
+
+impl Metric {
+    fn name(&self) -> &'static str {
+        match self {
+            Metric::Mean => "mean",
+            Metric::Median => "median",
+            Metric::P95 => "p95",
+        }
+    }
+}
+
+impl Config {
+    // --markdown and --field own stdout: nothing else may be printed there
+    fn machine_output(&self) -> bool {
+        self.markdown || self.field.is_some()
+    }
+
+    // The elapsed time of a running command is ticked onto the progress
+    // line, which only exists on a terminal
+    fn run_ticker(&self) -> bool {
+        !self.quiet && !self.machine_output() && !matches!(self.output, OutputMode::Inherit)
+            && io::stdout().is_terminal()
+    }
+}
+
+// The single value printed with --field
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Min,
+    Max,
+    Avg,
+    Median,
+    P95,
+    Stddev,
+    Total,
+    Cpu,
+    Success,
+    Fail,
+    BytesPerSec,
+    ItemsPerSec,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Field> {
+        match name {
+            "min" => Some(Field::Min),
+            "max" => Some(Field::Max),
+            "avg" | "mean" => Some(Field::Avg),
+            "median" => Some(Field::Median),
+            "p95" => Some(Field::P95),
+            "stddev" => Some(Field::Stddev),
+            "total" => Some(Field::Total),
+            "cpu" => Some(Field::Cpu),
+            "success" => Some(Field::Success),
+            "fail" => Some(Field::Fail),
+            "bytes_per_sec" => Some(Field::BytesPerSec),
+            "items_per_sec" => Some(Field::ItemsPerSec),
+            _ => None,
+        }
+    }
+}
+
+// A single command to benchmark; `name` is how it is shown in the output
+// and `params` holds the --param values substituted into it
+#[derive(Debug, Clone)]
+struct Job {
+    // Label shown in output; the command line itself unless one was given
+    name: String,
+    line: String,
     command: String,
     args: Vec<String>,
+    params: Vec<(String, String)>,
+    // Copies started at once per run with --jobs
+    concurrency: usize,
+}
+
+impl Job {
+    fn substitute(&self, name: &str, value: &str) -> Job {
+        let placeholder = format!("{{{}}}", name);
+        let mut params = self.params.clone();
+        params.push((name.to_string(), value.to_string()));
+        Job {
+            name: self.name.replace(&placeholder, value),
+            line: self.line.replace(&placeholder, value),
+            command: self.command.replace(&placeholder, value),
+            args: self.args.iter().map(|a| a.replace(&placeholder, value)).collect(),
+            params,
+            concurrency: self.concurrency,
+        }
+    }
+}
+
+// Find a `{NAME}` placeholder that no --param replaced
+fn find_placeholder(text: &str) -> Option<&str> {
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        if let Some(end) = after.find('}') {
+            let name = &after[..end];
+            if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Some(&rest[start..start + end + 2]);
+            }
+        }
+        rest = after;
+    }
+    None
+}
+
+// Expand every job into the cross product of the --param values
+fn expand_params(jobs: Vec<Job>, params: &[(String, Vec<String>)]) -> Result<Vec<Job>, String> {
+    let mut jobs = jobs;
+    for (name, values) in params {
+        jobs = jobs.iter()
+            .flat_map(|job| values.iter().map(move |value| job.substitute(name, value)))
+            .collect();
+    }
+
+    if !params.is_empty() {
+        for job in &jobs {
+            let texts = std::iter::once(&job.command).chain(job.args.iter());
+            if let Some(placeholder) = texts.filter_map(|t| find_placeholder(t)).next() {
+                return Err(format!("Unknown placeholder {} in command", placeholder));
+            }
+        }
+    }
+
+    Ok(jobs)
 }
 
 #[derive(Debug)]
@@ -44,6 +365,59 @@ struct ExecutionStats {
     total_time: Duration,
     success_count: usize,
     fail_count: usize,
+    // How every measured run ended
+    exits: Vec<RunExit>,
+    // When every measured run started
+    starts: Vec<SystemTime>,
+    // Failed attempts rerun before every measured run was recorded
+    retries: Vec<usize>,
+    // Durations of the single copies with --jobs; `times` holds whole waves
+    instance_times: Vec<Duration>,
+    // User plus system CPU time of every measured run, empty when unsupported
+    cpu_times: Vec<Duration>,
+    // Stdout hash of every measured run with --check-output, of every copy
+    // with --jobs
+    output_hashes: Vec<u64>,
+    // Run number, exit and the tail of stderr of every failed run
+    failures: Vec<(usize, RunExit, String)>,
+    outliers: Vec<Duration>,
+    warmup_runs: usize,
+    warmup_stop: WarmupStop,
+    // --max-time stopped the measured runs before the plan was complete
+    budget_exhausted: bool,
+}
+
+// How a run ended: an exit code or the signal that killed it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum RunExit {
+    Code(i32),
+    Signal(i32),
+}
+
+impl std::fmt::Display for RunExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RunExit::Code(code) => write!(f, "exit {}", code),
+            RunExit::Signal(signal) => write!(f, "signal {}", signal),
+        }
+    }
+}
+
+// Lines of stderr kept for each failed run, unless --verbose keeps all
+const FAILURE_TAIL_LINES: usize = 20;
+
+// Bytes of stderr kept in memory per run while draining the pipe, unless
+// --verbose keeps all
+const STDERR_BUFFER: usize = 64 * 1024;
+
+// Why the warmup phase ended
+#[derive(Debug)]
+enum WarmupStop {
+    Fixed,
+    Converged,
+    Cap,
+    // The --max-time budget ran out before warmup finished
+    Budget,
 }
 
 impl ExecutionStats {
@@ -56,11 +430,23 @@ impl ExecutionStats {
             total_time: Duration::from_secs(0),
             success_count: 0,
             fail_count: 0,
+            exits: Vec::new(),
+            starts: Vec::new(),
+            retries: Vec::new(),
+            instance_times: Vec::new(),
+            cpu_times: Vec::new(),
+            output_hashes: Vec::new(),
+            failures: Vec::new(),
+            outliers: Vec::new(),
+            warmup_runs: 0,
+            warmup_stop: WarmupStop::Fixed,
+            budget_exhausted: false,
         }
     }
 
-    fn add_execution(&mut self, duration: Duration, success: bool) {
+    fn add_execution(&mut self, duration: Duration, exit: RunExit, success: bool) {
         self.times.push_back(duration);
+        self.exits.push(exit);
         self.total_time += duration;
 
         if success {
@@ -80,6 +466,189 @@ impl ExecutionStats {
         // Recalculate the average
         self.avg = self.total_time / self.times.len() as u32;
     }
+
+    // Move runs further than `threshold` median absolute deviations from the
+    // median into `outliers` and recompute min/max/avg/total without them.
+    // Raw timings stay in `times`.
+    fn trim_outliers(&mut self, threshold: f64) {
+        let secs: Vec<f64> = self.times.iter().map(|t| t.as_secs_f64()).collect();
+        let center = median(&secs);
+        let deviations: Vec<f64> = secs.iter().map(|t| (t - center).abs()).collect();
+        let mad = median(&deviations);
+
+        // All runs (or most of them) are identical; nothing stands out
+        if mad == 0.0 {
+            return;
+        }
+
+        let (kept, outliers): (Vec<Duration>, Vec<Duration>) = self.times.iter()
+            .partition(|t| (t.as_secs_f64() - center).abs() <= threshold * mad);
+
+        self.outliers = outliers;
+        self.total_time = kept.iter().sum();
+        self.min = kept.iter().min().cloned().unwrap_or_default();
+        self.max = kept.iter().max().cloned().unwrap_or_default();
+        self.avg = self.total_time / kept.len() as u32;
+    }
+
+    // Timings that count towards the statistics, i.e. without outliers
+    fn samples(&self) -> Vec<Duration> {
+        let mut outliers = self.outliers.clone();
+        self.times.iter().cloned().filter(|t| {
+            match outliers.iter().position(|o| o == t) {
+                Some(index) => {
+                    outliers.swap_remove(index);
+                    false
+                }
+                None => true,
+            }
+        }).collect()
+    }
+
+    // Mean, min and max of the single copies' durations with --jobs
+    fn instance_summary(&self) -> Option<(Duration, Duration, Duration)> {
+        let min = *self.instance_times.iter().min()?;
+        let max = *self.instance_times.iter().max()?;
+        let mean = self.instance_times.iter().sum::<Duration>() / self.instance_times.len() as u32;
+        Some((mean, min, max))
+    }
+
+    // Mean, min and max of `work` per second over the counted runs
+    fn throughput(&self, work: u64) -> Option<(f64, f64, f64)> {
+        let rates: Vec<f64> = self.samples().iter()
+            .filter(|t| !t.is_zero())
+            .map(|t| work as f64 / t.as_secs_f64())
+            .collect();
+        if rates.is_empty() {
+            return None;
+        }
+        let mean = rates.iter().sum::<f64>() / rates.len() as f64;
+        let min = rates.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = rates.iter().cloned().fold(0.0, f64::max);
+        Some((mean, min, max))
+    }
+
+    // Standard deviation relative to the mean
+    fn variation(&self) -> f64 {
+        let mean = self.avg.as_secs_f64();
+        if mean > 0.0 {
+            self.stddev().as_secs_f64() / mean
+        } else {
+            0.0
+        }
+    }
+
+    fn is_noisy(&self, config: &Config) -> bool {
+        self.variation() > config.noise_threshold
+    }
+
+    fn median(&self) -> Duration {
+        let secs: Vec<f64> = self.samples().iter().map(|t| t.as_secs_f64()).collect();
+        if secs.is_empty() {
+            return Duration::from_secs(0);
+        }
+        Duration::from_secs_f64(median(&secs))
+    }
+
+    // Nearest-rank percentile of the counted timings
+    fn percentile(&self, p: f64) -> Duration {
+        let mut samples = self.samples();
+        if samples.is_empty() {
+            return Duration::from_secs(0);
+        }
+        samples.sort();
+        let rank = (p / 100.0 * samples.len() as f64).ceil() as usize;
+        samples[rank.clamp(1, samples.len()) - 1]
+    }
+
+    fn metric(&self, metric: Metric) -> Duration {
+        match metric {
+            Metric::Mean => self.avg,
+            Metric::Median => self.median(),
+            Metric::P95 => self.percentile(95.0),
+        }
+    }
+
+    // Mean CPU time per run, if it was measured for every run
+    fn cpu_mean(&self) -> Option<Duration> {
+        if self.cpu_times.is_empty() || self.cpu_times.len() != self.times.len() {
+            return None;
+        }
+        Some(self.cpu_times.iter().sum::<Duration>() / self.cpu_times.len() as u32)
+    }
+
+    // Runs (numbered from 1) whose output differs from the most common one
+    fn differing_outputs(&self) -> Vec<usize> {
+        let common = self.output_hashes.iter()
+            .max_by_key(|&&hash| self.output_hashes.iter().filter(|&&h| h == hash).count());
+        match common {
+            Some(&common) => self.output_hashes.iter().enumerate()
+                .filter(|&(_, &hash)| hash != common)
+                .map(|(index, _)| index + 1)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // How often each exit occurred, codes in ascending order and signals last
+    fn exit_counts(&self) -> Vec<(RunExit, usize)> {
+        let mut counts: Vec<(RunExit, usize)> = Vec::new();
+        for &exit in &self.exits {
+            match counts.iter_mut().find(|(e, _)| *e == exit) {
+                Some(entry) => entry.1 += 1,
+                None => counts.push((exit, 1)),
+            }
+        }
+        counts.sort();
+        counts
+    }
+
+    // Mean, minimum and maximum of the last `size` raw timings
+    fn window(&self, size: usize) -> Option<(Duration, Duration, Duration)> {
+        if self.times.len() < size {
+            return None;
+        }
+        let recent = self.times.range(self.times.len() - size..);
+        let min = recent.clone().min().cloned().unwrap_or_default();
+        let max = recent.clone().max().cloned().unwrap_or_default();
+        let mean = recent.sum::<Duration>() / size as u32;
+        Some((mean, min, max))
+    }
+
+    // Half-width of the 95% confidence interval of the mean, if there are
+    // enough runs to estimate one
+    fn confidence_interval(&self) -> Option<Duration> {
+        let n = self.samples().len();
+        if n < 2 {
+            return None;
+        }
+        let t = T_TABLE_95.get(n - 2).cloned().unwrap_or(1.96);
+        Some(Duration::from_secs_f64(t * self.stddev().as_secs_f64() / (n as f64).sqrt()))
+    }
+
+    // Sample standard deviation of the counted timings
+    fn stddev(&self) -> Duration {
+        let samples = self.samples();
+        if samples.len() < 2 {
+            return Duration::from_secs(0);
+        }
+        let mean = self.avg.as_secs_f64();
+        let variance = samples.iter()
+            .map(|t| (t.as_secs_f64() - mean).powi(2))
+            .sum::<f64>() / (samples.len() - 1) as f64;
+        Duration::from_secs_f64(variance.sqrt())
+    }
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
 }
 
 fn parse_args() -> Result<Config, String> {
@@ -92,12 +661,59 @@ fn parse_args() -> Result<Config, String> {
     let mut config = Config {
         iterations: 3,
         warmup: 1,
+        auto_warmup: false,
+        warmup_threshold: 5.0,
+        max_warmup: 10,
+        min_time: None,
+        max_iterations: 1000,
+        max_time: None,
+        started: Instant::now(),
+        trim_outliers: false,
+        outlier_threshold: 3.0,
+        noise_threshold: HIGH_VARIANCE,
+        load_average: None,
         quiet: false,
+        verbose: false,
         simple: false,
+        markdown: false,
+        field: None,
+        color: false,
+        histogram: None,
+        window: None,
+        bytes: None,
+        items: None,
+        output: OutputMode::Discard,
+        keep_warmup_output: false,
+        force: false,
+        prepare: None,
+        cleanup: None,
+        cwd: None,
+        stdin_file: None,
+        env: Vec::new(),
+        env_clear: false,
+        suite: None,
+        sequential: false,
+        seed: None,
+        params: Vec::new(),
+        concurrency: vec![1],
+        export_json: None,
+        save_baseline: None,
+        baseline: None,
+        max_regression: None,
+        compare_metric: Metric::Mean,
+        check_output: false,
+        success_codes: vec![0],
+        ignore_exit_code: false,
+        retries: 0,
+        commands: Vec::new(),
         command: String::new(),
         args: Vec::new(),
     };
 
+    let mut iterations_set = false;
+    let mut warmup_set = false;
+    let mut color_choice = "auto".to_string();
+    let mut labeled: Vec<(String, Option<String>)> = Vec::new();
     let mut i = 1;
     while i < args.len() {
         match args[i].as_str() {
@@ -115,6 +731,32 @@ fn parse_args() -> Result<Config, String> {
                 if config.iterations < 1 {
                     return Err("Iterations must be at least 1".to_string());
                 }
+                iterations_set = true;
+            }
+            "--min-time" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for min-time".to_string());
+                }
+                config.min_time = Some(parse_duration(&args[i])?);
+            }
+            "--max-time" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for max-time".to_string());
+                }
+                config.max_time = Some(parse_duration(&args[i])?);
+            }
+            "--max-iterations" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for max-iterations".to_string());
+                }
+                config.max_iterations = args[i].parse()
+                    .map_err(|_| "Invalid max-iterations value")?;
+                if config.max_iterations < MIN_TIMED_RUNS {
+                    return Err(format!("Max iterations must be at least {}", MIN_TIMED_RUNS));
+                }
             }
             "-w" | "--warmup" => {
                 i += 1;
@@ -123,6 +765,61 @@ fn parse_args() -> Result<Config, String> {
                 }
                 config.warmup = args[i].parse()
                     .map_err(|_| "Invalid warmup value")?;
+                warmup_set = true;
+            }
+            "--auto-warmup" => {
+                config.auto_warmup = true;
+            }
+            "--warmup-threshold" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for warmup-threshold".to_string());
+                }
+                config.warmup_threshold = args[i].trim_end_matches('%').parse()
+                    .map_err(|_| "Invalid warmup-threshold value")?;
+                if config.warmup_threshold.is_nan() || config.warmup_threshold <= 0.0 {
+                    return Err("Warmup threshold must be positive".to_string());
+                }
+            }
+            "--max-warmup" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for max-warmup".to_string());
+                }
+                config.max_warmup = args[i].parse()
+                    .map_err(|_| "Invalid max-warmup value")?;
+                if config.max_warmup < 2 {
+                    return Err("Max warmup must be at least 2".to_string());
+                }
+            }
+            "--trim-outliers" => {
+                config.trim_outliers = true;
+            }
+            "--outlier-threshold" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for outlier-threshold".to_string());
+                }
+                config.outlier_threshold = args[i].parse()
+                    .map_err(|_| "Invalid outlier-threshold value")?;
+                if config.outlier_threshold.is_nan() || config.outlier_threshold <= 0.0 {
+                    return Err("Outlier threshold must be positive".to_string());
+                }
+            }
+            "--noise-threshold" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for noise-threshold".to_string());
+                }
+                let percent: f64 = args[i].parse()
+                    .map_err(|_| "Invalid noise-threshold value")?;
+                if percent.is_nan() || percent <= 0.0 {
+                    return Err("Noise threshold must be positive".to_string());
+                }
+                config.noise_threshold = percent / 100.0;
+            }
+            "-v" | "--verbose" => {
+                config.verbose = true;
             }
             "-q" | "--quiet" => {
                 config.quiet = true;
@@ -130,118 +827,2257 @@ fn parse_args() -> Result<Config, String> {
             "-s" | "--simple" => {
                 config.simple = true;
             }
-            _ => {
-                config.command = args[i].clone();
-                config.args = args[i + 1..].to_vec();
-                break;
+            "--markdown" => {
+                config.markdown = true;
             }
-        }
-        i += 1;
-    }
-
-    if config.command.is_empty() {
-        return Err("No command specified".to_string());
-    }
-
-    Ok(config)
-}
-
-fn format_duration(duration: Duration) -> String {
-    if duration.as_secs() > 0 {
-        format!("{:.3}s", duration.as_secs_f64())
-    } else {
-        format!("{}ms", duration.as_millis())
-    }
-}
-
-fn run_command(command: &str, args: &[String]) -> io::Result<(Duration, bool)> {
-    let start = Instant::now();
-    
-    let status = Command::new(command)
-        .args(args)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()?;
-    
-    let duration = start.elapsed();
-    Ok((duration, status.success()))
-}
-
-fn print_progress(current: usize, total: usize) {
-    print!("\rProgress: [{:3}%] {}/{} ", 
-           (current * 100) / total, 
-           current, 
-           total);
-    io::stdout().flush().unwrap();
-}
-
-fn print_results(stats: &ExecutionStats, config: &Config) {
-    if config.simple {
-        println!("min={} max={} avg={} total={} success={} fail={}",
-            format_duration(stats.min),
-            format_duration(stats.max),
-            format_duration(stats.avg),
-            format_duration(stats.total_time),
-            stats.success_count,
-            stats.fail_count
-        );
-    } else {
-        println!("\n=== Execution Summary ===");
-        println!("Command: {} {}", config.command, config.args.join(" "));
-        println!("Iterations: {}", stats.times.len());
-        println!("Successful: {}", stats.success_count);
-        println!("Failed: {}", stats.fail_count);
-        println!("\nTimings:");
-        println!("  Minimum: {}", format_duration(stats.min));
-        println!("  Maximum: {}", format_duration(stats.max));
-        println!("  Average: {}", format_duration(stats.avg));
-        println!("  Total:   {}", format_duration(stats.total_time));
-    }
-}
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let config = match parse_args() {
-        Ok(config) => config,
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            eprintln!("Try 'estimate --help' for more information.");
-            std::process::exit(1);
-        }
-    };
-
-    let total_runs = config.warmup + config.iterations;
-    let mut stats = ExecutionStats::new();
-
-    if !config.quiet {
-        println!("Running '{}' {} times (including {} warmup runs)...",
-                config.command,
-                total_runs,
-                config.warmup);
-    }
-
-    for i in 0..total_runs {
-        if !config.quiet {
-            print_progress(i + 1, total_runs);
-        }
-
-        match run_command(&config.command, &config.args) {
-            Ok((duration, success)) => {
-                if i >= config.warmup {
-                    stats.add_execution(duration, success);
+            "--success-exit-codes" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for success-exit-codes".to_string());
                 }
+                config.success_codes = args[i].split(',')
+                    .map(|code| code.trim().parse::<i32>()
+                        .map_err(|_| format!("Invalid exit code: {}", code)))
+                    .collect::<Result<Vec<i32>, String>>()?;
             }
-            Err(e) => {
-                eprintln!("\nError executing command: {}", e);
-                std::process::exit(1);
+            "--retries" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for retries".to_string());
+                }
+                config.retries = args[i].parse()
+                    .map_err(|_| "Invalid retries value")?;
             }
-        }
-    }
-
-    if !config.quiet {
-        println!();
-    }
-
-    print_results(&stats, &config);
-
-    Ok(())
-}
\ No newline at end of file
+            "--ignore-exit-code" => {
+                config.ignore_exit_code = true;
+            }
+            "--interleave" => {
+                config.sequential = false;
+            }
+            "--sequential" => {
+                config.sequential = true;
+            }
+            "--randomize" => {
+                // The seed is optional, so only consume a number
+                let seed = args.get(i + 1).and_then(|a| a.parse::<u64>().ok());
+                if seed.is_some() {
+                    i += 1;
+                }
+                config.seed = Some(seed.unwrap_or_else(|| {
+                    SystemTime::now().duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_nanos() as u64)
+                        .unwrap_or(0)
+                }));
+            }
+            "--color" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for color".to_string());
+                }
+                if !["auto", "always", "never"].contains(&args[i].as_str()) {
+                    return Err(format!("Invalid color value: {}", args[i]));
+                }
+                color_choice = args[i].clone();
+            }
+            "--suite" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for suite file".to_string());
+                }
+                config.suite = Some(PathBuf::from(&args[i]));
+            }
+            "--histogram" => {
+                // The bucket count is optional, so only consume a number
+                let buckets = args.get(i + 1).and_then(|a| a.parse::<usize>().ok());
+                if buckets.is_some() {
+                    i += 1;
+                }
+                let buckets = buckets.unwrap_or(10);
+                if buckets < 1 {
+                    return Err("Histogram needs at least 1 bucket".to_string());
+                }
+                config.histogram = Some(buckets);
+            }
+            "--window" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for window".to_string());
+                }
+                let size: usize = args[i].parse()
+                    .map_err(|_| "Invalid window value")?;
+                if size < 2 {
+                    return Err("Window must span at least 2 runs".to_string());
+                }
+                config.window = Some(size);
+            }
+            "--bytes" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for bytes".to_string());
+                }
+                let bytes = match args[i].strip_prefix("auto:") {
+                    Some(file) => fs::metadata(file)
+                        .map_err(|e| format!("Cannot read size of {}: {}", file, e))?
+                        .len(),
+                    None => args[i].parse().map_err(|_| format!("Invalid bytes value: {}", args[i]))?,
+                };
+                config.bytes = Some(bytes);
+            }
+            "--items" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for items".to_string());
+                }
+                config.items = Some(args[i].parse()
+                    .map_err(|_| format!("Invalid items value: {}", args[i]))?);
+            }
+            "--export-json" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for export-json".to_string());
+                }
+                config.export_json = Some(PathBuf::from(&args[i]));
+            }
+            "--save-baseline" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for save-baseline".to_string());
+                }
+                config.save_baseline = Some(PathBuf::from(&args[i]));
+            }
+            "--baseline" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for baseline".to_string());
+                }
+                config.baseline = Some(PathBuf::from(&args[i]));
+            }
+            "--max-regression" | "--threshold" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for max-regression".to_string());
+                }
+                let percent: f64 = args[i].trim_end_matches('%').parse()
+                    .map_err(|_| "Invalid max-regression value")?;
+                if percent.is_nan() || percent < 0.0 {
+                    return Err("Max regression must not be negative".to_string());
+                }
+                config.max_regression = Some(percent);
+            }
+            "--field" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for field".to_string());
+                }
+                config.field = Some(Field::parse(&args[i])
+                    .ok_or_else(|| format!("Unknown field: {}", args[i]))?);
+            }
+            "--compare-metric" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for compare-metric".to_string());
+                }
+                config.compare_metric = match args[i].as_str() {
+                    "mean" => Metric::Mean,
+                    "median" => Metric::Median,
+                    "p95" => Metric::P95,
+                    other => return Err(format!("Unknown compare metric: {}", other)),
+                };
+            }
+            "--prepare" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for prepare".to_string());
+                }
+                config.prepare = Some(args[i].clone());
+            }
+            "--cleanup" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for cleanup".to_string());
+                }
+                config.cleanup = Some(args[i].clone());
+            }
+            "--param" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for param".to_string());
+                }
+                let (name, values) = args[i].split_once('=')
+                    .ok_or_else(|| format!("Invalid param (expected NAME=v1,v2): {}", args[i]))?;
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    return Err(format!("Invalid param name: {}", name));
+                }
+                let values: Vec<String> = values.split(',').map(|v| v.to_string()).collect();
+                config.params.push((name.to_string(), values));
+            }
+            "-j" | "--jobs" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for jobs".to_string());
+                }
+                config.concurrency = args[i].split(',')
+                    .map(|n| match n.parse::<usize>() {
+                        Ok(n) if n > 0 => Ok(n),
+                        _ => Err(format!("Invalid jobs value: {}", n)),
+                    })
+                    .collect::<Result<Vec<usize>, String>>()?;
+            }
+            "--cwd" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for cwd".to_string());
+                }
+                config.cwd = Some(PathBuf::from(&args[i]));
+            }
+            "--stdin-file" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for stdin-file".to_string());
+                }
+                let path = PathBuf::from(&args[i]);
+                if !path.is_file() {
+                    return Err(format!("Stdin file not found: {}", args[i]));
+                }
+                config.stdin_file = Some(path);
+            }
+            "--env" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for env".to_string());
+                }
+                let (key, value) = args[i].split_once('=')
+                    .ok_or_else(|| format!("Invalid env (expected KEY=VALUE): {}", args[i]))?;
+                if key.is_empty() {
+                    return Err(format!("Invalid env (expected KEY=VALUE): {}", args[i]));
+                }
+                config.env.push((key.to_string(), value.to_string()));
+            }
+            "--env-clear" => {
+                config.env_clear = true;
+            }
+            "-c" | "--command" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for command".to_string());
+                }
+                labeled.push((args[i].clone(), None));
+            }
+            "--label" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for label".to_string());
+                }
+                match labeled.last_mut() {
+                    Some((_, label)) if label.is_none() => *label = Some(args[i].clone()),
+                    Some(_) => return Err(format!("Command already has a label: {}", args[i])),
+                    None => return Err("--label must follow -c/--command".to_string()),
+                }
+            }
+            "--check-output" => {
+                config.check_output = true;
+            }
+            "--show-output" => {
+                config.output = OutputMode::Inherit;
+            }
+            "--output-to" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for output directory".to_string());
+                }
+                config.output = OutputMode::Directory(PathBuf::from(&args[i]));
+                config.keep_warmup_output = true;
+            }
+            "--keep-output" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("Missing value for keep-output".to_string());
+                }
+                config.output = OutputMode::Directory(PathBuf::from(&args[i]));
+            }
+            "--keep-warmup-output" => {
+                config.keep_warmup_output = true;
+            }
+            "--force" => {
+                config.force = true;
+            }
+            // Everything after -- is the command, even if it looks like an option
+            "--" => {
+                if i + 1 < args.len() {
+                    config.command = args[i + 1].clone();
+                    config.args = args[i + 2..].to_vec();
+                }
+                break;
+            }
+            _ => {
+                config.command = args[i].clone();
+                config.args = args[i + 1..].to_vec();
+                break;
+            }
+        }
+        i += 1;
+    }
+
+    if config.suite.is_some() && !config.command.is_empty() {
+        return Err("--suite cannot be combined with a command".to_string());
+    }
+    if !labeled.is_empty() && (config.suite.is_some() || !config.command.is_empty()) {
+        return Err("-c/--command cannot be combined with --suite or a command".to_string());
+    }
+
+    // Unlabeled commands are called cmd1, cmd2, ... by position
+    let name_by_position = labeled.len() > 1 || labeled.iter().any(|(_, label)| label.is_some());
+    for (index, (line, label)) in labeled.into_iter().enumerate() {
+        let label = match label {
+            Some(label) => label,
+            None if name_by_position => format!("cmd{}", index + 1),
+            None => line.clone(),
+        };
+        if config.commands.iter().any(|(_, other)| *other == label) {
+            return Err(format!("Duplicate label: {}", label));
+        }
+        config.commands.push((line, label));
+    }
+    if config.check_output && matches!(config.output, OutputMode::Inherit) {
+        return Err("--check-output cannot be combined with --show-output".to_string());
+    }
+    // Concurrent copies would write the same scratch file
+    if config.check_output && matches!(config.output, OutputMode::Discard)
+        && config.concurrency.iter().any(|&n| n > 1) {
+        return Err("--check-output with --jobs needs --output-to".to_string());
+    }
+    if config.sequential && config.seed.is_some() {
+        return Err("--randomize cannot be combined with --sequential".to_string());
+    }
+
+    if config.command.is_empty() && config.suite.is_none() && config.commands.is_empty() {
+        return Err("No command specified".to_string());
+    }
+
+    let multiple = config.suite.is_some() || !config.params.is_empty() || config.commands.len() > 1
+        || config.concurrency.len() > 1;
+    if multiple && (config.baseline.is_some() || config.save_baseline.is_some()) {
+        return Err("Baselines are only supported for a single command".to_string());
+    }
+    if multiple && config.field.is_some() {
+        return Err("--field is only supported for a single command".to_string());
+    }
+    match config.field {
+        Some(Field::BytesPerSec) if config.bytes.is_none() => {
+            return Err("--field bytes_per_sec needs the work size from --bytes".to_string());
+        }
+        Some(Field::ItemsPerSec) if config.items.is_none() => {
+            return Err("--field items_per_sec needs the work size from --items".to_string());
+        }
+        _ => {}
+    }
+
+    if config.max_regression.is_some() && config.baseline.is_none() {
+        return Err("--max-regression requires --baseline".to_string());
+    }
+
+    // Machine-readable formats never get escape codes
+    config.color = !config.simple && !config.machine_output() && match color_choice.as_str() {
+        "always" => true,
+        "never" => false,
+        _ => env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal(),
+    };
+
+    // An explicit warmup count always wins over the adaptive one
+    if warmup_set {
+        config.auto_warmup = false;
+    }
+
+    if iterations_set && config.min_time.is_some() {
+        return Err("--min-time cannot be combined with --iterations".to_string());
+    }
+
+    Ok(config)
+}
+
+// Parse a duration such as "5s", "250ms", "2m" or a bare number of seconds
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, multiplier) = if let Some(n) = s.strip_suffix("ms") {
+        (n, 0.001)
+    } else if let Some(n) = s.strip_suffix('s') {
+        (n, 1.0)
+    } else if let Some(n) = s.strip_suffix('m') {
+        (n, 60.0)
+    } else if let Some(n) = s.strip_suffix('h') {
+        (n, 3600.0)
+    } else {
+        (s, 1.0)
+    };
+
+    let value: f64 = number.parse()
+        .map_err(|_| format!("Invalid duration: {}", s))?;
+    if !value.is_finite() || value <= 0.0 {
+        return Err(format!("Duration must be positive: {}", s));
+    }
+
+    Ok(Duration::from_secs_f64(value * multiplier))
+}
+
+fn round_to(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
+
+// Pick the unit that suits the duration and keep 3-4 significant digits,
+// e.g. 850ns, 12.4µs, 999ms, 1.234s, 59.95s, 2m 14.3s
+fn format_duration(duration: Duration) -> String {
+    let nanos = duration.as_nanos();
+    if nanos < 1000 {
+        return format!("{}ns", nanos);
+    }
+
+    let secs = duration.as_secs_f64();
+    // Seconds get a fourth digit so that e.g. 1.234s stays comparable
+    for &(scale, unit, limit, digits) in &[(1e-6, "µs", 1000.0, 3), (1e-3, "ms", 1000.0, 3),
+                                           (1.0, "s", 60.0, 4)] {
+        let value = secs / scale;
+        let mut decimals = (digits - 1 - value.log10().floor() as i32).max(0);
+        // Rounding can add a digit (9.9996 -> 10.000); drop a decimal then
+        if decimals > 0 && round_to(value, decimals) >= 10f64.powi(digits - decimals) {
+            decimals -= 1;
+        }
+        // Compare after rounding so 999.96µs becomes 1.00ms, not 1000µs
+        if round_to(value, decimals) < limit {
+            return format!("{:.*}{}", decimals as usize, value, unit);
+        }
+    }
+
+    let tenths = (secs * 10.0).round() as u64;
+    format!("{}m {:.1}s", tenths / 600, (tenths % 600) as f64 / 10.0)
+}
+
+// Directory the output of this run is saved in; warmup runs are only saved
+// when asked for
+fn saved_dir<'a>(config: &'a Config, label: &str) -> Option<&'a Path> {
+    match config.output {
+        OutputMode::Directory(ref dir) if config.keep_warmup_output || !label.contains("warmup-") => {
+            Some(dir)
+        }
+        _ => None,
+    }
+}
+
+// Where discarded stdout goes with --check-output
+fn scratch_path() -> PathBuf {
+    env::temp_dir().join(format!("estimate-{}.stdout", std::process::id()))
+}
+
+// File that keeps a run's stdout, if it is kept at all. With --check-output
+// discarded output goes to a scratch file so it can be hashed after timing.
+fn stdout_path(config: &Config, label: &str) -> Option<PathBuf> {
+    if let Some(dir) = saved_dir(config, label) {
+        return Some(dir.join(format!("{}.stdout", label)));
+    }
+    match config.output {
+        OutputMode::Inherit => None,
+        _ if config.check_output => Some(scratch_path()),
+        _ => None,
+    }
+}
+
+// Build the stdout/stderr handles for one run. Files are created here so that
+// opening them is not counted in the measured time. The child writes to them
+// directly, so large outputs cannot fill up a pipe.
+fn output_stdio(config: &Config, label: &str) -> io::Result<(Stdio, Stdio)> {
+    if let Some(dir) = saved_dir(config, label) {
+        let stdout = File::create(dir.join(format!("{}.stdout", label)))?;
+        let stderr = File::create(dir.join(format!("{}.stderr", label)))?;
+        return Ok((Stdio::from(stdout), Stdio::from(stderr)));
+    }
+    match config.output {
+        OutputMode::Inherit => Ok((Stdio::inherit(), Stdio::inherit())),
+        _ if config.check_output => {
+            let stdout = File::create(scratch_path())?;
+            Ok((Stdio::from(stdout), Stdio::piped()))
+        }
+        _ => Ok((Stdio::null(), Stdio::piped())),
+    }
+}
+
+// Apply --cwd, --env and --env-clear to the command and hooks alike
+fn apply_environment(cmd: &mut Command, config: &Config) {
+    if config.env_clear {
+        cmd.env_clear();
+    }
+    for (key, value) in &config.env {
+        cmd.env(key, value);
+    }
+    if let Some(ref dir) = config.cwd {
+        cmd.current_dir(dir);
+    }
+}
+
+// What one run of the benchmarked command produced
+struct RunResult {
+    started: SystemTime,
+    wall: Duration,
+    // User plus system time, where the platform can report it
+    cpu: Option<Duration>,
+    exit: RunExit,
+    stderr: Vec<u8>,
+    // Hash of stdout with --check-output, one per copy with --jobs
+    output_hashes: Vec<u64>,
+    // Duration of every copy when several ran at once
+    instances: Vec<Duration>,
+}
+
+// CPU time used by all waited-for children so far, via getrusage(2)
+#[cfg(target_os = "linux")]
+fn children_cpu_time() -> Option<Duration> {
+    use std::os::raw::{c_int, c_long};
+
+    #[repr(C)]
+    struct Timeval {
+        tv_sec: c_long,
+        tv_usec: c_long,
+    }
+
+    #[repr(C)]
+    struct Rusage {
+        ru_utime: Timeval,
+        ru_stime: Timeval,
+        // maxrss through nivcsw, unused here
+        rest: [c_long; 14],
+    }
+
+    extern "C" {
+        fn getrusage(who: c_int, usage: *mut Rusage) -> c_int;
+    }
+    const RUSAGE_CHILDREN: c_int = -1;
+
+    let mut usage: Rusage = unsafe { std::mem::zeroed() };
+    if unsafe { getrusage(RUSAGE_CHILDREN, &mut usage) } != 0 {
+        return None;
+    }
+    let micros = |tv: &Timeval| tv.tv_sec as u64 * 1_000_000 + tv.tv_usec as u64;
+    Some(Duration::from_micros(micros(&usage.ru_utime) + micros(&usage.ru_stime)))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn children_cpu_time() -> Option<Duration> {
+    None
+}
+
+// Time one run. Discarded stderr is drained into memory instead, so that
+// failures can be explained; only its last STDERR_BUFFER bytes are kept
+// unless --verbose asks for all of it.
+fn run_command(command: &str, args: &[String], config: &Config, label: &str)
+    -> io::Result<RunResult> {
+    let (stdout, stderr) = output_stdio(config, label)?;
+    let mut cmd = Command::new(command);
+    cmd.args(args).stdout(stdout).stderr(stderr);
+    apply_environment(&mut cmd, config);
+    // A fresh handle each run, so every run reads the file from the start
+    if let Some(ref path) = config.stdin_file {
+        cmd.stdin(File::open(path)?);
+    }
+
+    let started = SystemTime::now();
+    let cpu_before = children_cpu_time();
+    let start = Instant::now();
+    let mut child = cmd.spawn()?;
+    // A thread keeps the pipe empty so the child never blocks on it
+    let keep_all = config.verbose;
+    let drain = child.stderr.take().map(|mut pipe| thread::spawn(move || {
+        let mut kept = Vec::new();
+        let mut chunk = [0u8; 8192];
+        while let Ok(n) = pipe.read(&mut chunk) {
+            if n == 0 {
+                break;
+            }
+            kept.extend_from_slice(&chunk[..n]);
+            if !keep_all && kept.len() > STDERR_BUFFER {
+                kept.drain(..kept.len() - STDERR_BUFFER);
+            }
+        }
+        kept
+    }));
+    let status = child.wait()?;
+    let wall = start.elapsed();
+    let cpu = match (cpu_before, children_cpu_time()) {
+        (Some(before), Some(after)) => Some(after.saturating_sub(before)),
+        _ => None,
+    };
+
+    let stderr = drain.and_then(|handle| handle.join().ok()).unwrap_or_default();
+    let exit = match status.code() {
+        Some(code) => RunExit::Code(code),
+        None => RunExit::Signal(status.signal().unwrap_or(0)),
+    };
+    // Hashing happens only now, outside the measured time
+    let output_hashes = match stdout_path(config, label) {
+        Some(ref path) if config.check_output => vec![hash_file(path)?],
+        _ => Vec::new(),
+    };
+    Ok(RunResult { started, wall, cpu, exit, stderr, output_hashes, instances: Vec::new() })
+}
+
+// Start all copies of a --jobs run at once and wait for every one. The wave
+// reports the first failed copy, and CPU time for all of them together.
+fn run_wave(job: &Job, config: &Config, label: &str) -> io::Result<RunResult> {
+    if job.concurrency <= 1 {
+        return run_command(&job.command, &job.args, config, label);
+    }
+
+    let started = SystemTime::now();
+    let cpu_before = children_cpu_time();
+    let start = Instant::now();
+    let results: Vec<io::Result<RunResult>> = thread::scope(|scope| {
+        let handles: Vec<_> = (1..=job.concurrency)
+            .map(|copy| {
+                let label = format!("{}-{}", label, copy);
+                scope.spawn(move || run_command(&job.command, &job.args, config, &label))
+            })
+            .collect();
+        handles.into_iter()
+            .map(|handle| handle.join().unwrap_or_else(|_| Err(io::Error::other("run thread panicked"))))
+            .collect()
+    });
+    let wall = start.elapsed();
+    let cpu = match (cpu_before, children_cpu_time()) {
+        (Some(before), Some(after)) => Some(after.saturating_sub(before)),
+        _ => None,
+    };
+
+    let mut copies = results.into_iter().collect::<io::Result<Vec<RunResult>>>()?;
+    let instances = copies.iter().map(|copy| copy.wall).collect();
+    // Every copy saved its own stdout, so each is compared on its own
+    let output_hashes = copies.iter().flat_map(|copy| copy.output_hashes.iter().copied()).collect();
+    let failed = copies.iter().position(|copy| !is_success(copy.exit, config)).unwrap_or(0);
+    let copy = copies.swap_remove(failed);
+    let stderr = match saved_dir(config, label) {
+        Some(dir) => fs::read(dir.join(format!("{}-{}.stderr", label, failed + 1))).unwrap_or_default(),
+        None => copy.stderr,
+    };
+    Ok(RunResult { started, wall, cpu, exit: copy.exit, stderr, output_hashes, instances })
+}
+
+// 64-bit FNV-1a over a file, read in chunks so large outputs stay cheap
+fn hash_file(path: &Path) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut chunk = [0u8; 65536];
+    loop {
+        let n = file.read(&mut chunk)?;
+        if n == 0 {
+            return Ok(hash);
+        }
+        for &byte in &chunk[..n] {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+// Compact clock-style duration for progress lines, e.g. 45s, 2m20s, 1h05m
+fn format_clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+// The progress line currently shown, for RunTicker to extend
+static STATUS_LINE: Mutex<String> = Mutex::new(String::new());
+
+fn print_status(line: &str) {
+    *STATUS_LINE.lock().unwrap() = line.to_string();
+    draw_status(line);
+}
+
+// Rewrite the progress line in place, cut to the terminal width so it never wraps
+fn draw_status(line: &str) {
+    let width = terminal_width() - 1;
+    let line: String = line.chars().take(width).collect();
+    // Pad so a shorter line fully covers the previous one
+    print!("\r{:<w$}", line, w = width.min(64));
+    io::stdout().flush().unwrap();
+}
+
+// `current` is the run about to start and `elapsed` the time since run 1
+// started; the ETA extrapolates from the runs completed so far
+fn print_progress(current: usize, total: usize, elapsed: Duration) {
+    let mut line = format!("Progress: [{:3}%] {}/{}  elapsed {}",
+                           (current * 100) / total,
+                           current,
+                           total,
+                           format_clock(elapsed));
+    let completed = current - 1;
+    if completed > 0 {
+        let eta = elapsed.as_secs_f64() / completed as f64 * (total - completed) as f64;
+        line.push_str(&format!("  eta ~{}", format_clock(Duration::from_secs_f64(eta))));
+    }
+    print_status(&line);
+}
+
+// While a run is in progress, append its elapsed time to the progress line
+// every half second so a long run doesn't look hung. Dropping the ticker
+// puts the plain progress line back.
+struct RunTicker {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl RunTicker {
+    const INTERVAL: Duration = Duration::from_millis(500);
+
+    fn start(enabled: bool) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = if enabled {
+            let stop = Arc::clone(&stop);
+            Some(thread::spawn(move || {
+                let started = Instant::now();
+                loop {
+                    thread::park_timeout(RunTicker::INTERVAL);
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let line = STATUS_LINE.lock().unwrap().clone();
+                    draw_status(&format!("{}  run {}", line, format_clock(started.elapsed())));
+                }
+            }))
+        } else {
+            None
+        };
+        RunTicker { stop, handle }
+    }
+}
+
+impl Drop for RunTicker {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.stop.store(true, Ordering::SeqCst);
+            handle.thread().unpark();
+            handle.join().ok();
+            let line = STATUS_LINE.lock().unwrap();
+            if !line.is_empty() {
+                // Erase first, the ticked line may be longer than the padding
+                print!("\r\x1b[K");
+                draw_status(&line);
+            }
+        }
+    }
+}
+
+fn print_warmup_progress(current: usize, cap: usize) {
+    print_status(&format!("Warmup: {}/{} (max)", current, cap));
+}
+
+// In --min-time mode the ETA is simply the measured time still missing
+fn print_time_progress(measured: Duration, target: Duration, runs: usize, elapsed: Duration) {
+    let percent = (measured.as_secs_f64() / target.as_secs_f64() * 100.0).min(100.0);
+    print_status(&format!("Progress: [{:3.0}%] {}/{} ({} runs)  elapsed {}  eta ~{}",
+                          percent,
+                          format_duration(measured),
+                          format_duration(target),
+                          runs,
+                          format_clock(elapsed),
+                          format_clock(target.saturating_sub(measured))));
+}
+
+// One line per completed window, compared against the running mean so far
+fn print_window(stats: &ExecutionStats, size: usize, label: &str) {
+    if let Some((mean, min, max)) = stats.window(size) {
+        let runs = stats.times.len();
+        let overall = stats.avg.as_secs_f64();
+        let drift = if overall > 0.0 {
+            (mean.as_secs_f64() - overall) / overall * 100.0
+        } else {
+            0.0
+        };
+        println!("\r{}Runs {}-{}: mean {} (min {}, max {}), {:+.1}% vs overall",
+                 label, runs - size + 1, runs,
+                 format_duration(mean),
+                 format_duration(min),
+                 format_duration(max),
+                 drift);
+    }
+}
+
+// Bytes per second with binary prefixes
+fn format_byte_rate(rate: f64) -> String {
+    const UNITS: [&str; 5] = ["B/s", "KiB/s", "MiB/s", "GiB/s", "TiB/s"];
+    let mut value = rate;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+fn format_item_rate(rate: f64) -> String {
+    if rate >= 100.0 {
+        format!("{:.0} items/s", rate)
+    } else {
+        format!("{:.2} items/s", rate)
+    }
+}
+
+// Throughput columns that apply to this run, mean only
+fn throughput_summary(stats: &ExecutionStats, config: &Config) -> Vec<String> {
+    let mut parts = Vec::new();
+    if let Some((mean, _, _)) = config.bytes.and_then(|b| stats.throughput(b)) {
+        parts.push(format_byte_rate(mean));
+    }
+    if let Some((mean, _, _)) = config.items.and_then(|n| stats.throughput(n)) {
+        parts.push(format_item_rate(mean));
+    }
+    parts
+}
+
+fn format_ci(ci: Option<Duration>) -> String {
+    match ci {
+        Some(ci) => format_duration(ci),
+        None => "n/a".to_string(),
+    }
+}
+
+fn print_results(stats: &ExecutionStats, config: &Config, job: &Job) {
+    if config.simple {
+        let mut rates = String::new();
+        if let Some((mean, _, _)) = config.bytes.and_then(|b| stats.throughput(b)) {
+            rates.push_str(&format!(" bytes_per_sec={:.0}", mean));
+        }
+        if let Some((mean, _, _)) = config.items.and_then(|n| stats.throughput(n)) {
+            rates.push_str(&format!(" items_per_sec={:.2}", mean));
+        }
+        println!("min={} max={} avg={} ci95={} total={} success={} fail={}{}",
+            format_duration(stats.min),
+            format_duration(stats.max),
+            format_duration(stats.avg),
+            format_ci(stats.confidence_interval()),
+            format_duration(stats.total_time),
+            stats.success_count,
+            stats.fail_count,
+            rates
+        );
+    } else {
+        println!("\n=== Execution Summary ===");
+        if job.name != job.line {
+            println!("Label: {}", job.name);
+        }
+        println!("Command: {}", job.line);
+        match config.min_time {
+            Some(target) => println!("Iterations: {} (to reach {})",
+                                     stats.times.len(),
+                                     format_duration(target)),
+            None => println!("Iterations: {}", stats.times.len()),
+        }
+        if job.concurrency > 1 {
+            println!("Concurrency: {} copies per run (timings are for whole runs)", job.concurrency);
+        }
+        if let (true, Some(budget)) = (stats.budget_exhausted, config.max_time) {
+            println!("Note: the --max-time budget of {} ended the runs early",
+                     format_duration(budget));
+        }
+        match stats.warmup_stop {
+            WarmupStop::Fixed => {}
+            WarmupStop::Converged => println!("Warmup: {} runs (converged)", stats.warmup_runs),
+            WarmupStop::Cap => println!("Warmup: {} runs (stopped at cap, not converged)",
+                                        stats.warmup_runs),
+            WarmupStop::Budget => println!("Warmup: {} runs (cut short by --max-time)",
+                                           stats.warmup_runs),
+        }
+        if let Some(ref dir) = config.cwd {
+            println!("Working directory: {}", dir.display());
+        }
+        if let Some(ref path) = config.stdin_file {
+            println!("Stdin: {}", path.display());
+        }
+        if config.env_clear || !config.env.is_empty() {
+            let mut vars: Vec<String> = config.env.iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            if config.env_clear {
+                vars.insert(0, "(cleared)".to_string());
+            }
+            println!("Environment: {}", vars.join(" "));
+        }
+        if let Some(ref prepare) = config.prepare {
+            println!("Prepare: {} (not timed)", prepare);
+        }
+        if let Some(ref cleanup) = config.cleanup {
+            println!("Cleanup: {} (not timed)", cleanup);
+        }
+        println!("Successful: {}",
+                 style::paint(&stats.success_count.to_string(), style::GREEN, config.color));
+        if stats.fail_count > 0 {
+            println!("Failed: {}",
+                     style::paint(&stats.fail_count.to_string(), style::RED, config.color));
+        } else {
+            println!("Failed: {}", stats.fail_count);
+        }
+        let exits = stats.exit_counts();
+        if exits.iter().any(|&(exit, _)| exit != RunExit::Code(0)) {
+            let parts: Vec<String> = exits.iter()
+                .map(|&(exit, n)| format!("{}: {}", exit, n))
+                .collect();
+            println!("Exit codes: {}", parts.join(", "));
+        }
+        let retried = stats.retries.iter().filter(|&&n| n > 0).count();
+        if retried > 0 {
+            let total: usize = stats.retries.iter().sum();
+            println!("Retries: {} run{} needed retries ({} retr{} in total)",
+                     retried,
+                     if retried == 1 { "" } else { "s" },
+                     total,
+                     if total == 1 { "y" } else { "ies" });
+        }
+        if config.check_output {
+            let differing = stats.differing_outputs();
+            // With --jobs every copy of a run has an output of its own
+            let copies = stats.instance_times.len() / stats.times.len().max(1);
+            let noun = if copies > 1 { "copies" } else { "runs" };
+            if differing.is_empty() {
+                println!("Output: identical in all {} {}", stats.output_hashes.len(), noun);
+            } else {
+                let runs: Vec<String> = differing.iter()
+                    .map(|&index| if copies > 1 {
+                        format!("run {} copy {}", (index - 1) / copies + 1, (index - 1) % copies + 1)
+                    } else {
+                        index.to_string()
+                    })
+                    .collect();
+                let mut warning = format!("Warning: output differed in {}/{} {} ({}{})",
+                                          differing.len(), stats.output_hashes.len(), noun,
+                                          if copies > 1 { "" } else { "run " }, runs.join(", "));
+                if let OutputMode::Directory(ref dir) = config.output {
+                    warning.push_str(&format!(", saved in {}", dir.display()));
+                }
+                println!("{}", style::paint(&warning, style::YELLOW, config.color));
+            }
+        }
+        // Only the first failure unless asked for all of them
+        let shown = if config.verbose { stats.failures.len() } else { 1 };
+        for (run, exit, stderr) in stats.failures.iter().take(shown) {
+            if stderr.is_empty() {
+                println!("Run {} failed ({}) without stderr output", run, exit);
+            } else {
+                println!("Run {} failed ({}), {}:", run, exit,
+                         if config.verbose { "stderr" } else { "last lines of stderr" });
+                for line in stderr.lines() {
+                    println!("  | {}", line);
+                }
+            }
+        }
+        if stats.failures.len() > shown {
+            let more = stats.failures.len() - shown;
+            println!("({} more failed run{}, use --verbose to show {})",
+                     more,
+                     if more == 1 { "" } else { "s" },
+                     if more == 1 { "it" } else { "them" });
+        }
+        if !stats.outliers.is_empty() {
+            let values: Vec<String> = stats.outliers.iter()
+                .map(|&d| format_duration(d))
+                .collect();
+            println!("{} outlier{} removed: {}",
+                     stats.outliers.len(),
+                     if stats.outliers.len() == 1 { "" } else { "s" },
+                     values.join(", "));
+        }
+        println!("\nTimings:");
+        println!("  Minimum: {}", format_duration(stats.min));
+        println!("  Maximum: {}", format_duration(stats.max));
+        match stats.confidence_interval() {
+            Some(ci) => println!("  Average: {} ± {} (95% CI)",
+                                 format_duration(stats.avg),
+                                 format_duration(ci)),
+            None => println!("  Average: {} ± n/a (95% CI)", format_duration(stats.avg)),
+        }
+        println!("  Total:   {}", format_duration(stats.total_time));
+        if let Some((mean, min, max)) = stats.instance_summary() {
+            println!("  Per copy: {} (min {}, max {})",
+                     format_duration(mean), format_duration(min), format_duration(max));
+        }
+        // Low CPU next to high wall time means the command mostly waits (I/O, sleep)
+        if let Some(cpu) = stats.cpu_mean() {
+            let all_wall = stats.times.iter().sum::<Duration>().as_secs_f64();
+            let all_cpu = stats.cpu_times.iter().sum::<Duration>().as_secs_f64();
+            if all_wall > 0.0 {
+                println!("  CPU:     {} (user+sys, {:.0}% of wall time)",
+                         format_duration(cpu), all_cpu / all_wall * 100.0);
+            } else {
+                println!("  CPU:     {} (user+sys)", format_duration(cpu));
+            }
+        }
+        let rates = [
+            (config.bytes, "Bytes", format_byte_rate as fn(f64) -> String),
+            (config.items, "Items", format_item_rate),
+        ];
+        for (work, name, format) in rates.iter() {
+            if let Some((mean, min, max)) = work.and_then(|w| stats.throughput(w)) {
+                println!("  {}:   {} (min {}, max {})", name, format(mean), format(min), format(max));
+            }
+        }
+        if let Some(size) = config.window {
+            if let Some((mean, min, max)) = stats.window(size) {
+                println!("  Last {}: {} (min {}, max {})",
+                         size,
+                         format_duration(mean),
+                         format_duration(min),
+                         format_duration(max));
+            }
+        }
+
+        if stats.is_noisy(config) {
+            println!("\n{}", style::paint(&noise_warning(stats), style::YELLOW, config.color));
+        }
+    }
+}
+
+fn noise_warning(stats: &ExecutionStats) -> String {
+    format!("Warning: high variance (standard deviation is {:.0}% of the mean); \
+             these numbers are unreliable. Try more iterations (-n) or a quieter system",
+            stats.variation() * 100.0)
+}
+
+// The 1-minute load average, on systems that have /proc/loadavg
+fn read_load_average() -> Option<f64> {
+    let text = fs::read_to_string("/proc/loadavg").ok()?;
+    text.split_whitespace().next()?.parse().ok()
+}
+
+fn cpu_count() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+// More runnable tasks than CPUs means the command competed for them
+fn system_loaded(config: &Config) -> bool {
+    config.load_average.is_some_and(|load| load > cpu_count() as f64)
+}
+
+// Durations in whole nanoseconds, counts and rates as plain integers
+fn print_field(field: Field, stats: &ExecutionStats, config: &Config) {
+    let nanos = |d: Duration| d.as_nanos().to_string();
+    let rate = |work: Option<u64>| work.and_then(|w| stats.throughput(w))
+        .map(|(mean, _, _)| format!("{:.0}", mean));
+    let value = match field {
+        Field::Min => Some(nanos(stats.min)),
+        Field::Max => Some(nanos(stats.max)),
+        Field::Avg => Some(nanos(stats.avg)),
+        Field::Median => Some(nanos(stats.median())),
+        Field::P95 => Some(nanos(stats.percentile(95.0))),
+        Field::Stddev => Some(nanos(stats.stddev())),
+        Field::Total => Some(nanos(stats.total_time)),
+        Field::Cpu => stats.cpu_mean().map(nanos),
+        Field::Success => Some(stats.success_count.to_string()),
+        Field::Fail => Some(stats.fail_count.to_string()),
+        Field::BytesPerSec => rate(config.bytes),
+        Field::ItemsPerSec => rate(config.items),
+    };
+    match value {
+        Some(value) => println!("{}", value),
+        None => {
+            eprintln!("Error: the requested field is not available for this run");
+            std::process::exit(1);
+        }
+    }
+}
+
+// Wrap a command in a Markdown code span, keeping `|` from splitting the cell
+fn markdown_code(text: &str) -> String {
+    let escaped = text.replace('|', "\\|");
+    if text.contains('`') {
+        format!("`` {} ``", escaped)
+    } else {
+        format!("`{}`", escaped)
+    }
+}
+
+// Print one row per command; relative speed is measured against the fastest.
+// All durations in the table share one unit so the columns line up.
+fn print_markdown_table(rows: &[(String, &ExecutionStats)]) {
+    let fastest = rows.iter()
+        .map(|(_, stats)| stats.avg.as_secs_f64())
+        .fold(f64::INFINITY, f64::min);
+    let in_seconds = rows.iter().any(|(_, stats)| stats.max.as_secs() > 0);
+    let fmt = |d: Duration| if in_seconds {
+        format!("{:.3}s", d.as_secs_f64())
+    } else {
+        format!("{:.1}ms", d.as_secs_f64() * 1000.0)
+    };
+
+    println!("| Command | Mean ± σ | Min | Max | Relative |");
+    println!("|:---|---:|---:|---:|---:|");
+    for (name, stats) in rows {
+        let relative = if fastest > 0.0 {
+            stats.avg.as_secs_f64() / fastest
+        } else {
+            1.0
+        };
+        println!("| {} | {} ± {} | {} | {} | {:.2} |",
+                 markdown_code(name),
+                 fmt(stats.avg),
+                 fmt(stats.stddev()),
+                 fmt(stats.min),
+                 fmt(stats.max),
+                 relative);
+    }
+}
+
+// Round a bucket width up to 1, 2 or 5 times a power of ten
+fn nice_width(raw: f64) -> f64 {
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let fraction = raw / magnitude;
+    let nice = if fraction <= 1.0 {
+        1.0
+    } else if fraction <= 2.0 {
+        2.0
+    } else if fraction <= 5.0 {
+        5.0
+    } else {
+        10.0
+    };
+    nice * magnitude
+}
+
+fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .filter(|&c: &usize| c >= 40)
+        .unwrap_or(80)
+}
+
+fn print_histogram(samples: &[Duration], buckets: usize) {
+    if samples.is_empty() {
+        return;
+    }
+
+    let secs: Vec<f64> = samples.iter().map(|d| d.as_secs_f64()).collect();
+    let min = secs.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = secs.iter().cloned().fold(0.0, f64::max);
+
+    // Label everything in the unit that suits the slowest run
+    let (scale, unit) = if max >= 1.0 {
+        (1.0, "s")
+    } else if max >= 0.001 {
+        (1e3, "ms")
+    } else {
+        (1e6, "µs")
+    };
+
+    println!("\nHistogram:");
+
+    // Constant-time commands: a single row instead of one bar and empty rows
+    let range = (max - min) * scale;
+    if range < 1e-9 {
+        println!("  {:.3}{}  {}  {}", min * scale, unit, "#".repeat(40), samples.len());
+        return;
+    }
+
+    let width = nice_width(range / buckets as f64);
+    let start = (min * scale / width).floor() * width;
+    let count = (((max * scale - start) / width).floor() as usize + 1).min(buckets + 1);
+    let mut counts = vec![0usize; count];
+    for s in &secs {
+        let index = ((s * scale - start) / width).floor() as usize;
+        counts[index.min(count - 1)] += 1;
+    }
+
+    let decimals = if width >= 1.0 { 0 } else { (-width.log10().floor()) as usize };
+    let labels: Vec<String> = (0..count)
+        .map(|i| {
+            let low = start + width * i as f64;
+            format!("{:.*}-{:.*}{}", decimals, low, decimals, low + width, unit)
+        })
+        .collect();
+
+    let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let most = *counts.iter().max().unwrap_or(&1);
+    let count_width = most.to_string().len();
+    let bar_space = terminal_width().saturating_sub(label_width + count_width + 6).max(10);
+
+    for (label, &n) in labels.iter().zip(&counts) {
+        let bar = (n * bar_space).div_ceil(most);
+        println!("  {:>lw$}  {:<bw$}  {:>cw$}",
+                 label,
+                 "#".repeat(bar),
+                 n,
+                 lw = label_width,
+                 bw = bar_space,
+                 cw = count_width);
+    }
+}
+
+// Rough significance hint: do the 95% intervals of two means overlap?
+fn intervals_overlap(a: &ExecutionStats, b: &ExecutionStats) -> Option<bool> {
+    let (ci_a, ci_b) = (a.confidence_interval()?, b.confidence_interval()?);
+    let (low_a, high_a) = (a.avg.saturating_sub(ci_a), a.avg + ci_a);
+    let (low_b, high_b) = (b.avg.saturating_sub(ci_b), b.avg + ci_b);
+    Some(low_a <= high_b && low_b <= high_a)
+}
+
+// `results` must already be sorted fastest first
+fn print_suite_summary(results: &[(&Job, ExecutionStats)], config: &Config) {
+    let color = config.color;
+    let best = &results[0].1;
+    let fastest = best.avg.as_secs_f64();
+    let with_throughput = config.bytes.is_some() || config.items.is_some();
+
+    println!("\n=== Suite Summary ===");
+    print!("{:>3}  {:>10}  {:>10}  {:>9}  {:<11}  ", "#", "Mean", "95% CI", "Relative", "vs fastest");
+    if with_throughput {
+        print!("{:<28}  ", "Throughput");
+    }
+    println!("Command");
+    for (rank, (job, stats)) in results.iter().enumerate() {
+        let relative = if fastest > 0.0 {
+            stats.avg.as_secs_f64() / fastest
+        } else {
+            1.0
+        };
+        let hint = if rank == 0 {
+            "-"
+        } else {
+            match intervals_overlap(best, stats) {
+                Some(true) => "overlaps",
+                Some(false) => "distinct",
+                None => "n/a",
+            }
+        };
+        // Throughput relative to the fastest command, so 0.50x is half as much work per second
+        let throughput = if with_throughput {
+            let mut parts = throughput_summary(stats, config);
+            if relative > 0.0 && !parts.is_empty() {
+                parts.push(format!("{:.2}x", 1.0 / relative));
+            }
+            format!("{:<28}  ", parts.join(", "))
+        } else {
+            String::new()
+        };
+        let row = format!("{:>3}  {:>10}  {:>10}  {:>8.2}x  {:<11}  {}{}",
+                          rank + 1,
+                          format_duration(stats.avg),
+                          format!("± {}", format_ci(stats.confidence_interval())),
+                          relative,
+                          hint,
+                          throughput,
+                          job.name);
+        println!("{}", style::paint(&row, style::BOLD, color && rank == 0));
+    }
+}
+
+// One row per parameter combination, keyed by the parameter values
+fn print_param_table(params: &[(String, Vec<String>)], results: &[(&Job, ExecutionStats)]) {
+    let widths: Vec<usize> = params.iter()
+        .map(|(name, values)| {
+            values.iter().map(|v| v.len()).chain(std::iter::once(name.len())).max().unwrap_or(0)
+        })
+        .collect();
+
+    println!("\n=== Parameter Sweep ===");
+    for ((name, _), width) in params.iter().zip(&widths) {
+        print!("{:<w$}  ", name, w = width);
+    }
+    println!("{:>10}  {:>10}  {:>10}  {:>10}", "Mean", "StdDev", "Min", "Max");
+
+    for (job, stats) in results {
+        for ((_, value), width) in job.params.iter().zip(&widths) {
+            print!("{:<w$}  ", value, w = width);
+        }
+        println!("{:>10}  {:>10}  {:>10}  {:>10}",
+                 format_duration(stats.avg),
+                 format_duration(stats.stddev()),
+                 format_duration(stats.min),
+                 format_duration(stats.max));
+    }
+}
+
+// How much slower each copy got when several ran at once, against the same
+// command run alone (-j 1)
+fn print_contention(results: &[(&Job, ExecutionStats)]) {
+    println!("\n=== Contention ===");
+    for (job, stats) in results.iter().filter(|(job, _)| job.concurrency > 1) {
+        let alone = results.iter()
+            .find(|(other, _)| other.concurrency == 1 && other.line == job.line && other.params == job.params)
+            .map(|(_, stats)| stats.avg);
+        if let (Some(alone), Some((mean, _, _))) = (alone, stats.instance_summary()) {
+            if alone.is_zero() {
+                continue;
+            }
+            println!("  {}: {} per copy vs {} alone ({:.2}x)",
+                     job.name,
+                     format_duration(mean),
+                     format_duration(alone),
+                     mean.as_secs_f64() / alone.as_secs_f64());
+        }
+    }
+}
+
+// Check that a command can be started, looking it up in PATH unless it
+// names a path itself. Both are resolved as the run will see them: from
+// --cwd, and with the PATH given by --env. After --env-clear without a PATH
+// the search path is up to the system, so the first run has to tell.
+fn find_command(command: &str, config: &Config) -> Result<PathBuf, String> {
+    let executable = |path: &Path| fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false);
+    let cwd = config.cwd.as_deref().unwrap_or(Path::new(""));
+    if command.contains('/') {
+        let path = cwd.join(command);
+        if !path.exists() {
+            return Err(format!("{}: command not found", command));
+        }
+        if !executable(&path) {
+            return Err(format!("{}: not an executable file", command));
+        }
+        return Ok(path);
+    }
+    let paths = match config.env.iter().rev().find(|(key, _)| key == "PATH") {
+        Some((_, value)) => Some(value.into()),
+        None if config.env_clear => return Ok(PathBuf::from(command)),
+        None => env::var_os("PATH"),
+    };
+    paths
+        .and_then(|paths: std::ffi::OsString| env::split_paths(&paths)
+            .map(|dir| cwd.join(dir).join(command))
+            .find(|path| executable(path)))
+        .ok_or_else(|| format!("{}: command not found", command))
+}
+
+// Read a suite file: one shell command per line, '#' starts a comment line
+fn read_suite(path: &Path) -> io::Result<Vec<Job>> {
+    let content = fs::read_to_string(path)?;
+    Ok(content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| Job {
+            name: line.to_string(),
+            line: line.to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), line.to_string()],
+            params: Vec::new(),
+            concurrency: 1,
+        })
+        .collect())
+}
+
+// Run a --prepare/--cleanup hook through the shell, capturing its stderr
+fn run_hook(hook: &str, config: &Config) -> io::Result<Output> {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", hook])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    apply_environment(&mut cmd, config);
+    cmd.output()
+}
+
+// One run of the job, with the untimed hooks around it
+fn run_iteration(job: &Job, config: &Config, label: &str) -> RunResult {
+    if let Some(ref prepare) = config.prepare {
+        match run_hook(prepare, config) {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => {
+                eprintln!("\nError: Prepare command failed ({}): {}", output.status, prepare);
+                io::stderr().write_all(&output.stderr).ok();
+                std::process::exit(output.status.code().unwrap_or(1));
+            }
+            Err(e) => {
+                eprintln!("\nError executing prepare command: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let ticker = RunTicker::start(config.run_ticker());
+    let result = run_wave(job, config, label);
+    drop(ticker);
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("\nError executing command: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(ref cleanup) = config.cleanup {
+        match run_hook(cleanup, config) {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => {
+                eprintln!("\nWarning: Cleanup command failed ({}): {}", output.status, cleanup);
+                io::stderr().write_all(&output.stderr).ok();
+            }
+            Err(e) => eprintln!("\nWarning: Failed to execute cleanup command: {}", e),
+        }
+    }
+
+    result
+}
+
+// Small xorshift64* generator, so a shuffled run order can be reproduced from its seed
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck on a zero state
+        let state = seed ^ 0x9e37_79b9_7f4a_7c15;
+        Rng(if state == 0 { 1 } else { state })
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // Fisher-Yates shuffle
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+// Announce a job before its first run
+fn print_header(job: &Job, config: &Config) {
+    match (config.min_time, config.auto_warmup) {
+        (Some(target), true) => println!("Running '{}' for at least {} (plus adaptive warmup)...",
+                                         job.name,
+                                         format_duration(target)),
+        (None, true) => println!("Running '{}' {} times (plus adaptive warmup)...",
+                                 job.name,
+                                 config.iterations),
+        (Some(target), false) => println!("Running '{}' for at least {} (plus {} warmup runs)...",
+                                          job.name,
+                                          format_duration(target),
+                                          config.warmup),
+        (None, false) => println!("Running '{}' {} times (including {} warmup runs)...",
+                                  job.name,
+                                  config.warmup + config.iterations,
+                                  config.warmup),
+    }
+}
+
+// Warmup phase: a fixed count, or until consecutive runs agree. With
+// `standalone` the progress counts warmup runs on their own instead of as
+// the first part of the whole run.
+fn warm_up(job: &Job, config: &Config, prefix: &str, stats: &mut ExecutionStats,
+           show_progress: bool, standalone: bool) {
+    let mut previous: Option<Duration> = None;
+    let mut run = 0;
+    let started = Instant::now();
+    loop {
+        let cap = if config.auto_warmup { config.max_warmup } else { config.warmup };
+        if run >= cap {
+            if config.auto_warmup {
+                stats.warmup_stop = WarmupStop::Cap;
+            }
+            break;
+        }
+        if over_budget(config) {
+            stats.warmup_stop = WarmupStop::Budget;
+            break;
+        }
+
+        if show_progress {
+            match config.min_time {
+                _ if config.auto_warmup => print_warmup_progress(run + 1, cap),
+                _ if standalone => print_progress(run + 1, config.warmup, started.elapsed()),
+                Some(_) => print_progress(run + 1, config.warmup, started.elapsed()),
+                None => print_progress(run + 1, config.warmup + config.iterations,
+                                       started.elapsed()),
+            }
+        }
+
+        let label = format!("{}warmup-{:03}", prefix, run + 1);
+        let duration = run_iteration(job, config, &label).wall;
+        run += 1;
+
+        if config.auto_warmup {
+            if let Some(prev) = previous {
+                let change = (duration.as_secs_f64() - prev.as_secs_f64()).abs()
+                    / prev.as_secs_f64().max(f64::MIN_POSITIVE);
+                if change * 100.0 < config.warmup_threshold {
+                    stats.warmup_stop = WarmupStop::Converged;
+                    break;
+                }
+            }
+            previous = Some(duration);
+        }
+    }
+    stats.warmup_runs = run;
+}
+
+fn is_success(exit: RunExit, config: &Config) -> bool {
+    match exit {
+        _ if config.ignore_exit_code => true,
+        RunExit::Code(code) => config.success_codes.contains(&code),
+        RunExit::Signal(_) => false,
+    }
+}
+
+// Last lines of a failed run's stderr, or all of it with --verbose, from
+// memory or from its saved file
+fn stderr_tail(stderr: &[u8], config: &Config, label: &str) -> String {
+    let text = match config.output {
+        // Waves of --jobs copies already read the failed copy's file
+        OutputMode::Directory(ref dir) => fs::read(dir.join(format!("{}.stderr", label)))
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .unwrap_or_else(|_| String::from_utf8_lossy(stderr).into_owned()),
+        _ => String::from_utf8_lossy(stderr).into_owned(),
+    };
+    if config.verbose {
+        return text.trim_end().to_string();
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    lines[lines.len().saturating_sub(FAILURE_TAIL_LINES)..].join("\n")
+}
+
+// Whether a job has collected all the measured runs it needs
+fn measuring_done(stats: &mut ExecutionStats, config: &Config) -> bool {
+    let measured = stats.times.len();
+    let planned_done = match config.min_time {
+        Some(target) => measured >= config.max_iterations
+            || (measured >= MIN_TIMED_RUNS && stats.total_time >= target),
+        None => measured >= config.iterations,
+    };
+    if !planned_done && over_budget(config) {
+        stats.budget_exhausted = true;
+        return true;
+    }
+    planned_done
+}
+
+fn over_budget(config: &Config) -> bool {
+    match config.max_time {
+        Some(budget) => config.started.elapsed() >= budget,
+        None => false,
+    }
+}
+
+// One measured run, plus the rolling window report when one completes
+fn measure(job: &Job, config: &Config, prefix: &str, stats: &mut ExecutionStats, window_label: &str) {
+    let label = format!("{}run-{:03}", prefix, stats.times.len() + 1);
+    let mut result = run_iteration(job, config, &label);
+    let mut success = is_success(result.exit, config);
+    // A retry replaces the failed attempt, also in the saved output
+    let mut retries = 0;
+    while !success && retries < config.retries && !over_budget(config) {
+        retries += 1;
+        result = run_iteration(job, config, &label);
+        success = is_success(result.exit, config);
+    }
+    stats.add_execution(result.wall, result.exit, success);
+    stats.starts.push(result.started);
+    stats.retries.push(retries);
+    stats.instance_times.extend(result.instances);
+    if let Some(cpu) = result.cpu {
+        stats.cpu_times.push(cpu);
+    }
+    stats.output_hashes.extend(result.output_hashes);
+
+    // Inherited stderr has already been seen on the terminal
+    if !success && !matches!(config.output, OutputMode::Inherit) {
+        stats.failures.push((stats.times.len(), result.exit,
+                             stderr_tail(&result.stderr, config, &label)));
+    }
+
+    if let Some(size) = config.window {
+        if !config.quiet && !config.machine_output() && stats.times.len().is_multiple_of(size) {
+            print_window(stats, size, window_label);
+        }
+    }
+}
+
+fn finish(stats: &mut ExecutionStats, config: &Config) {
+    if let (true, Some(target)) = (stats.budget_exhausted, config.min_time) {
+        if stats.total_time < target {
+            eprintln!("Warning: --max-time stopped before --min-time {} was reached ({} measured)",
+                      format_duration(target), format_duration(stats.total_time));
+        }
+    }
+
+    if config.trim_outliers {
+        if stats.times.len() < MIN_OUTLIER_SAMPLES {
+            eprintln!("Warning: --trim-outliers needs at least {} runs, skipping",
+                      MIN_OUTLIER_SAMPLES);
+        } else {
+            stats.trim_outliers(config.outlier_threshold);
+        }
+    }
+}
+
+// Run the warmup and measured iterations of one job.
+// `prefix` keeps saved output of different jobs apart.
+fn benchmark(job: &Job, config: &Config, prefix: &str) -> ExecutionStats {
+    let mut stats = ExecutionStats::new();
+    // Progress output would interleave with the command's own output
+    let chatty = !config.quiet && !config.machine_output();
+    let show_progress = chatty && !matches!(config.output, OutputMode::Inherit);
+
+    if chatty {
+        print_header(job, config);
+    }
+
+    let started = Instant::now();
+    warm_up(job, config, prefix, &mut stats, show_progress, false);
+    let measuring_started = Instant::now();
+
+    while !measuring_done(&mut stats, config) {
+        if show_progress {
+            let measured = stats.times.len();
+            match config.min_time {
+                Some(target) => print_time_progress(stats.total_time, target, measured,
+                                                    measuring_started.elapsed()),
+                None if config.auto_warmup => print_progress(measured + 1, config.iterations,
+                                                             measuring_started.elapsed()),
+                None => print_progress(config.warmup + measured + 1,
+                                       config.warmup + config.iterations,
+                                       started.elapsed()),
+            }
+        }
+
+        measure(job, config, prefix, &mut stats, "");
+    }
+
+    if show_progress {
+        if let Some(target) = config.min_time {
+            print_time_progress(stats.total_time, target, stats.times.len(),
+                                measuring_started.elapsed());
+        }
+        println!();
+    }
+
+    finish(&mut stats, config);
+    stats
+}
+
+// Benchmark several jobs round-robin (A, B, A, B, ...) so that drift such as
+// thermal throttling or cache warmup affects all of them alike. Each job
+// still finishes its warmup before any measured run starts.
+fn benchmark_interleaved(jobs: &[Job], config: &Config) -> Vec<ExecutionStats> {
+    let chatty = !config.quiet && !config.machine_output();
+    let show_progress = chatty && !matches!(config.output, OutputMode::Inherit);
+    let prefixes: Vec<String> = (0..jobs.len()).map(|i| job_prefix(i, jobs.len())).collect();
+    let mut all_stats: Vec<ExecutionStats> = jobs.iter().map(|_| ExecutionStats::new()).collect();
+
+    for ((job, prefix), stats) in jobs.iter().zip(&prefixes).zip(all_stats.iter_mut()) {
+        if chatty {
+            print_header(job, config);
+        }
+        warm_up(job, config, prefix, stats, show_progress, true);
+        if show_progress && stats.warmup_runs > 0 {
+            println!();
+        }
+    }
+
+    if chatty {
+        match config.seed {
+            Some(seed) => println!("Interleaving measured runs of {} commands in random order (seed {})...",
+                                   jobs.len(), seed),
+            None => println!("Interleaving measured runs of {} commands...", jobs.len()),
+        }
+    }
+
+    // Every round runs each command once; with a seed the order within a
+    // round is shuffled
+    let mut rng = config.seed.map(Rng::new);
+    let mut order: Vec<usize> = (0..jobs.len()).collect();
+    let mut round = 0;
+    let started = Instant::now();
+    loop {
+        let mut ran = false;
+        if let Some(ref mut rng) = rng {
+            rng.shuffle(&mut order);
+        }
+        for (position, &index) in order.iter().enumerate() {
+            let job = &jobs[index];
+            let stats = &mut all_stats[index];
+            if measuring_done(stats, config) {
+                continue;
+            }
+
+            if show_progress {
+                let measured = stats.times.len();
+                match config.min_time {
+                    Some(target) => print_time_progress(stats.total_time, target, measured,
+                                                        started.elapsed()),
+                    None => print_progress(round * jobs.len() + position + 1,
+                                           config.iterations * jobs.len(),
+                                           started.elapsed()),
+                }
+                print!("[cmd {}/{}] ", index + 1, jobs.len());
+                io::stdout().flush().unwrap();
+            }
+
+            let window_label = format!("[cmd {}/{}] ", index + 1, jobs.len());
+            measure(job, config, &prefixes[index], stats, &window_label);
+            ran = true;
+        }
+        if !ran {
+            break;
+        }
+        round += 1;
+    }
+
+    if show_progress {
+        println!();
+    }
+
+    for stats in &mut all_stats {
+        finish(stats, config);
+    }
+    all_stats
+}
+
+// Output file prefix that keeps the runs of several commands apart
+fn job_prefix(index: usize, count: usize) -> String {
+    if count > 1 {
+        format!("cmd-{:02}-", index + 1)
+    } else {
+        String::new()
+    }
+}
+
+fn json_escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn baseline_json(job: &Job, stats: &ExecutionStats, config: &Config) -> String {
+    let times: Vec<String> = stats.times.iter()
+        .map(|t| format!("{:.9}", t.as_secs_f64()))
+        .collect();
+    // Runs killed by a signal have no exit code
+    let exit_codes: Vec<String> = stats.exits.iter()
+        .map(|exit| match exit {
+            RunExit::Code(code) => code.to_string(),
+            RunExit::Signal(_) => "null".to_string(),
+        })
+        .collect();
+    let cwd = match config.cwd {
+        Some(ref dir) => format!("\"{}\"", json_escape(&dir.to_string_lossy())),
+        None => "null".to_string(),
+    };
+    let env: Vec<String> = config.env.iter()
+        .map(|(key, value)| format!("\"{}\": \"{}\"", json_escape(key), json_escape(value)))
+        .collect();
+    format!("{{\n  \"command\": \"{}\",\n  \"label\": \"{}\",\n  \"cwd\": {},\n  \"env_clear\": {},\n  \
+             \"env\": {{{}}},\n  \"iterations\": {},\n  \"mean\": {:.9},\n  \
+             \"median\": {:.9},\n  \"p95\": {:.9},\n  \"min\": {:.9},\n  \"max\": {:.9},\n  \
+             \"stddev\": {:.9},\n  \"ci95\": {},\n  \"times\": [{}],\n  \
+             \"exit_codes\": [{}],\n  \"cpu_mean\": {},\n  \"seed\": {},\n  \
+             {},\n  \"noisy\": {},\n  \"system_loaded\": {}\n}}\n",
+            json_escape(&job.line),
+            json_escape(&job.name),
+            cwd,
+            config.env_clear,
+            env.join(", "),
+            stats.times.len(),
+            stats.avg.as_secs_f64(),
+            stats.median().as_secs_f64(),
+            stats.percentile(95.0).as_secs_f64(),
+            stats.min.as_secs_f64(),
+            stats.max.as_secs_f64(),
+            stats.stddev().as_secs_f64(),
+            stats.confidence_interval()
+                .map(|ci| format!("{:.9}", ci.as_secs_f64()))
+                .unwrap_or_else(|| "null".to_string()),
+            times.join(", "),
+            exit_codes.join(", "),
+            stats.cpu_mean()
+                .map(|cpu| format!("{:.9}", cpu.as_secs_f64()))
+                .unwrap_or_else(|| "null".to_string()),
+            config.seed.map_or("null".to_string(), |seed| seed.to_string()),
+            throughput_json(stats, config, "  "),
+            stats.is_noisy(config),
+            system_loaded(config))
+}
+
+// Work per run and mean throughput as raw numbers, one field per line
+fn throughput_json(stats: &ExecutionStats, config: &Config, indent: &str) -> String {
+    let rate = |work: Option<u64>| work.and_then(|w| stats.throughput(w))
+        .map_or("null".to_string(), |(mean, _, _)| format!("{:.3}", mean));
+    let count = |work: Option<u64>| work.map_or("null".to_string(), |w| w.to_string());
+    format!("\"bytes\": {},\n{i}\"bytes_per_second\": {},\n{i}\
+             \"items\": {},\n{i}\"items_per_second\": {}",
+            count(config.bytes), rate(config.bytes),
+            count(config.items), rate(config.items),
+            i = indent)
+}
+
+// Bump when the fields of the --export-json file change; 2 added "ci95"
+const EXPORT_VERSION: u32 = 2;
+
+fn unix_seconds(time: SystemTime) -> String {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => format!("{:.6}", since.as_secs_f64()),
+        Err(_) => "null".to_string(),
+    }
+}
+
+fn optional_seconds(duration: Option<Duration>) -> String {
+    duration.map_or("null".to_string(), |d| format!("{:.9}", d.as_secs_f64()))
+}
+
+// Full record of a benchmark for --export-json: the settings and every
+// measured run of every command, in the order the commands were given
+fn export_json(results: &[(&Job, ExecutionStats)], config: &Config) -> String {
+    let env: Vec<String> = config.env.iter()
+        .map(|(key, value)| format!("\"{}\": \"{}\"", json_escape(key), json_escape(value)))
+        .collect();
+    let cwd = match config.cwd {
+        Some(ref dir) => format!("\"{}\"", json_escape(&dir.to_string_lossy())),
+        None => "null".to_string(),
+    };
+
+    let commands: Vec<String> = results.iter().map(|(job, stats)| {
+        let params: Vec<String> = job.params.iter()
+            .map(|(name, value)| format!("\"{}\": \"{}\"", json_escape(name), json_escape(value)))
+            .collect();
+        let runs: Vec<String> = stats.times.iter().enumerate().map(|(index, time)| {
+            let (code, signal) = match stats.exits[index] {
+                RunExit::Code(code) => (code.to_string(), "null".to_string()),
+                RunExit::Signal(signal) => ("null".to_string(), signal.to_string()),
+            };
+            // Per-run CPU time is only known when every run reported one
+            let cpu = if stats.cpu_times.len() == stats.times.len() {
+                Some(stats.cpu_times[index])
+            } else {
+                None
+            };
+            format!("{{\"start\": {}, \"duration\": {:.9}, \"cpu\": {}, \
+                     \"exit_code\": {}, \"signal\": {}, \"success\": {}, \"retries\": {}}}",
+                    unix_seconds(stats.starts[index]),
+                    time.as_secs_f64(),
+                    optional_seconds(cpu),
+                    code,
+                    signal,
+                    is_success(stats.exits[index], config),
+                    stats.retries[index])
+        }).collect();
+
+        format!("    {{\n      \"label\": \"{}\",\n      \"command\": \"{}\",\n      \
+                 \"params\": {{{}}},\n      \"warmup_runs\": {},\n      \
+                 \"mean\": {:.9},\n      \"median\": {:.9},\n      \"min\": {:.9},\n      \
+                 \"max\": {:.9},\n      \"stddev\": {:.9},\n      \"ci95\": {},\n      \
+                 \"cpu_mean\": {},\n      \
+                 {},\n      \"noisy\": {},\n      \"concurrency\": {},\n      \
+                 \"copy_durations\": [{}],\n      \"runs\": [\n        {}\n      ]\n    }}",
+                json_escape(&job.name),
+                json_escape(&job.line),
+                params.join(", "),
+                stats.warmup_runs,
+                stats.avg.as_secs_f64(),
+                stats.median().as_secs_f64(),
+                stats.min.as_secs_f64(),
+                stats.max.as_secs_f64(),
+                stats.stddev().as_secs_f64(),
+                optional_seconds(stats.confidence_interval()),
+                optional_seconds(stats.cpu_mean()),
+                throughput_json(stats, config, "      "),
+                stats.is_noisy(config),
+                job.concurrency,
+                stats.instance_times.iter()
+                    .map(|t| format!("{:.9}", t.as_secs_f64()))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                runs.join(",\n        "))
+    }).collect();
+
+    format!("{{\n  \"version\": {},\n  \"created\": {},\n  \"cwd\": {},\n  \
+             \"env_clear\": {},\n  \"env\": {{{}}},\n  \"prepare\": {},\n  \
+             \"cleanup\": {},\n  \"seed\": {},\n  \"load_average\": {},\n  \
+             \"system_loaded\": {},\n  \"commands\": [\n{}\n  ]\n}}\n",
+            EXPORT_VERSION,
+            unix_seconds(SystemTime::now()),
+            cwd,
+            config.env_clear,
+            env.join(", "),
+            config.prepare.as_ref()
+                .map_or("null".to_string(), |cmd| format!("\"{}\"", json_escape(cmd))),
+            config.cleanup.as_ref()
+                .map_or("null".to_string(), |cmd| format!("\"{}\"", json_escape(cmd))),
+            config.seed.map_or("null".to_string(), |seed| seed.to_string()),
+            config.load_average.map_or("null".to_string(), |load| format!("{:.2}", load)),
+            system_loaded(config),
+            commands.join(",\n"))
+}
+
+// Write through a temporary file in the same directory and rename it into
+// place, so an interrupted run never leaves a truncated file behind
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file name"))?;
+    let temp = path.with_file_name(format!(".{}.{}.tmp", name.to_string_lossy(),
+                                           std::process::id()));
+    let result = fs::write(&temp, contents).and_then(|_| fs::rename(&temp, path));
+    if result.is_err() {
+        fs::remove_file(&temp).ok();
+    }
+    result
+}
+
+// Find the raw text of a top-level value in a baseline file written by
+// `baseline_json`; this is not a general JSON parser.
+fn json_value<'a>(json: &'a str, key: &str) -> Option<&'a str> {
+    let pattern = format!("\"{}\":", key);
+    let start = json.find(&pattern)? + pattern.len();
+    let rest = json[start..].trim_start();
+
+    if let Some(body) = rest.strip_prefix('"') {
+        let mut escaped = false;
+        for (i, c) in body.char_indices() {
+            match c {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => return Some(&body[..i]),
+                _ => escaped = false,
+            }
+        }
+        None
+    } else {
+        let end = rest.find([',', '\n', '}']).unwrap_or(rest.len());
+        Some(rest[..end].trim())
+    }
+}
+
+fn json_unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('u') => {
+                let code: String = chars.by_ref().take(4).collect();
+                if let Some(c) = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                    result.push(c);
+                }
+            }
+            Some(other) => result.push(other),
+            None => {}
+        }
+    }
+    result
+}
+
+// Compare against a saved baseline; returns false on a regression past the limit
+fn compare_baseline(path: &Path, command: &str, stats: &ExecutionStats, config: &Config)
+    -> Result<bool, String> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read baseline {}: {}", path.display(), e))?;
+
+    let metric = config.compare_metric;
+    let baseline_secs: f64 = json_value(&json, metric.name())
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| format!("Baseline {} has no valid '{}' value", path.display(), metric.name()))?;
+    let baseline = Duration::from_secs_f64(baseline_secs);
+
+    if let Some(saved) = json_value(&json, "command").map(json_unescape) {
+        if saved != command {
+            eprintln!("Warning: baseline was recorded for a different command: {}", saved);
+        }
+    }
+
+    let current = stats.metric(metric);
+    let change = if baseline_secs > 0.0 {
+        (current.as_secs_f64() - baseline_secs) / baseline_secs * 100.0
+    } else {
+        0.0
+    };
+
+    if let Some(limit) = config.max_regression {
+        if change > limit {
+            eprintln!("regression: {} vs baseline {} ({:+.0}%, limit {}%)",
+                      format_duration(current),
+                      format_duration(baseline),
+                      change,
+                      limit);
+            return Ok(false);
+        }
+    }
+
+    if !config.quiet && !config.machine_output() {
+        println!("\nBaseline ({}): {} vs baseline {} ({:+.0}%)",
+                 metric.name(),
+                 format_duration(current),
+                 format_duration(baseline),
+                 change);
+    }
+
+    Ok(true)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = match parse_args() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("Try 'estimate --help' for more information.");
+            std::process::exit(1);
+        }
+    };
+
+    let jobs = match config.suite {
+        Some(ref path) => match read_suite(path) {
+            Ok(jobs) if jobs.is_empty() => {
+                eprintln!("Error: Suite file {} contains no commands", path.display());
+                std::process::exit(1);
+            }
+            Ok(jobs) => jobs,
+            Err(e) => {
+                eprintln!("Error: Failed to read suite file {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None if !config.commands.is_empty() => config.commands.iter()
+            .map(|(line, label)| Job {
+                name: label.clone(),
+                line: line.clone(),
+                command: "sh".to_string(),
+                args: vec!["-c".to_string(), line.clone()],
+                params: Vec::new(),
+                concurrency: 1,
+            })
+            .collect(),
+        None => {
+            let line = if config.args.is_empty() {
+                config.command.clone()
+            } else {
+                format!("{} {}", config.command, config.args.join(" "))
+            };
+            vec![Job {
+                name: line.clone(),
+                line,
+                command: config.command.clone(),
+                args: config.args.clone(),
+                params: Vec::new(),
+                concurrency: 1,
+            }]
+        }
+    };
+
+    let jobs = match expand_params(jobs, &config.params) {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // A missing command would only fail on the first run, after the banner;
+    // shell command lines are left for the shell to resolve
+    if config.suite.is_none() && config.commands.is_empty() {
+        for job in &jobs {
+            if let Err(e) = find_command(&job.command, &config) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // One benchmark per --jobs count, named after it when there are several
+    let counts = &config.concurrency;
+    let jobs: Vec<Job> = jobs.into_iter()
+        .flat_map(|job| counts.iter().map(move |&n| {
+            let mut job = job.clone();
+            if counts.len() > 1 {
+                job.name = format!("{} (-j {})", job.name, n);
+            }
+            job.concurrency = n;
+            job
+        }))
+        .collect();
+
+    if let OutputMode::Directory(ref dir) = config.output {
+        if let Err(e) = fs::create_dir_all(dir) {
+            eprintln!("Error: Failed to create output directory {}: {}", dir.display(), e);
+            std::process::exit(1);
+        }
+        // Mixing the files of two benchmarks would make both misleading
+        let earlier = fs::read_dir(dir).map(|entries| entries.flatten().any(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.ends_with(".stdout") || name.ends_with(".stderr")
+        }));
+        if earlier.unwrap_or(false) && !config.force {
+            eprintln!("Error: {} already holds output of an earlier run (use --force to overwrite)",
+                      dir.display());
+            std::process::exit(1);
+        }
+    }
+
+    config.load_average = read_load_average();
+    if system_loaded(&config) {
+        let warning = format!("Warning: the system is busy (load average {:.2} with {} CPUs); \
+                               timings will be inflated by other processes",
+                              config.load_average.unwrap_or(0.0), cpu_count());
+        eprintln!("{}", style::paint(&warning, style::YELLOW,
+                                     config.color && io::stderr().is_terminal()));
+    }
+    let config = config;
+
+    // Runs of several commands only show per-command detail when not quiet
+    let detailed = !config.machine_output() && (jobs.len() == 1 || !config.quiet);
+    let report = |job: &Job, stats: &ExecutionStats| {
+        if detailed {
+            print_results(stats, &config, job);
+            if let Some(buckets) = config.histogram {
+                print_histogram(&stats.samples(), buckets);
+            }
+        }
+    };
+
+    let mut results = Vec::new();
+    if jobs.len() > 1 && !config.sequential {
+        for (job, stats) in jobs.iter().zip(benchmark_interleaved(&jobs, &config)) {
+            report(job, &stats);
+            results.push((job, stats));
+        }
+    } else {
+        for (index, job) in jobs.iter().enumerate() {
+            let stats = benchmark(job, &config, &job_prefix(index, jobs.len()));
+            report(job, &stats);
+            results.push((job, stats));
+        }
+    }
+
+    // Written before ranking reorders the results
+    if let Some(ref path) = config.export_json {
+        if let Err(e) = write_atomically(path, &export_json(&results, &config)) {
+            eprintln!("Error: Failed to write {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+
+    let ranked = config.suite.is_some() || config.commands.len() > 1;
+    if ranked {
+        results.sort_by_key(|(_, stats)| stats.avg);
+    }
+
+    // Tables show labels, so spell out the commands once for reproducibility
+    if !config.machine_output() && !config.simple && jobs.iter().any(|job| job.name != job.line) {
+        println!("\nCommands:");
+        for job in &jobs {
+            println!("  {}: {}", job.name, job.line);
+        }
+    }
+
+    if let Some(field) = config.field {
+        print_field(field, &results[0].1, &config);
+    } else if config.markdown {
+        let rows: Vec<(String, &ExecutionStats)> = results.iter()
+            .map(|(job, stats)| (job.name.clone(), stats))
+            .collect();
+        print_markdown_table(&rows);
+    } else if ranked {
+        print_suite_summary(&results, &config);
+    } else if !config.params.is_empty() {
+        print_param_table(&config.params, &results);
+    }
+    if config.concurrency.contains(&1) && config.concurrency.len() > 1
+        && !config.machine_output() && !config.simple {
+        print_contention(&results);
+    }
+    if let Some(seed) = config.seed {
+        if !config.machine_output() && !config.simple && jobs.len() > 1 {
+            println!("\nRun order randomized with seed {}", seed);
+        }
+    }
+
+    // Noise is part of the summary, unless there was none to show it in
+    if !detailed || config.simple {
+        for (job, stats) in results.iter().filter(|(_, stats)| stats.is_noisy(&config)) {
+            eprintln!("{} ({})", noise_warning(stats), job.name);
+        }
+    }
+
+    // A budget too small for the warmup leaves nothing meaningful to report
+    let budget_failed = results.iter()
+        .any(|(_, stats)| matches!(stats.warmup_stop, WarmupStop::Budget));
+
+    // Remove the scratch file that discarded output was hashed from
+    if config.check_output {
+        fs::remove_file(scratch_path()).ok();
+    }
+
+    // Baselines are only allowed for a single command
+    let (job, stats) = &results[0];
+
+    if let Some(ref path) = config.save_baseline {
+        if let Err(e) = fs::write(path, baseline_json(job, stats, &config)) {
+            eprintln!("Error: Failed to write baseline {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+
+    // The first run of a perf gate records what later runs are held to
+    if let Some(ref path) = config.baseline {
+        if !path.exists() {
+            if let Err(e) = fs::write(path, baseline_json(job, stats, &config)) {
+                eprintln!("Error: Failed to write baseline {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+            if !config.machine_output() {
+                println!("\nBaseline: {} did not exist, saved these results as the new baseline",
+                         path.display());
+            }
+        } else {
+            match compare_baseline(path, &job.line, stats, &config) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(2),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    if budget_failed {
+        eprintln!("Error: --max-time budget ran out before warmup finished");
+        std::process::exit(3);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_duration_unit_boundaries() {
+        assert_eq!(format_duration(Duration::from_nanos(850)), "850ns");
+        assert_eq!(format_duration(Duration::from_nanos(999)), "999ns");
+        assert_eq!(format_duration(Duration::from_nanos(1_000)), "1.00µs");
+        assert_eq!(format_duration(Duration::from_micros(999)), "999µs");
+        assert_eq!(format_duration(Duration::from_nanos(999_960)), "1.00ms");
+        assert_eq!(format_duration(Duration::from_millis(1)), "1.00ms");
+        assert_eq!(format_duration(Duration::from_micros(999_960)), "1.000s");
+        assert_eq!(format_duration(Duration::from_millis(1_234)), "1.234s");
+        assert_eq!(format_duration(Duration::from_millis(59_950)), "59.95s");
+        assert_eq!(format_duration(Duration::from_millis(59_999)), "1m 0.0s");
+        assert_eq!(format_duration(Duration::from_secs(60)), "1m 0.0s");
+        assert_eq!(format_duration(Duration::from_millis(134_300)), "2m 14.3s");
+    }
+}
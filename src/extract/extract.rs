@@ -1,29 +1,70 @@
 use std::env;
 use std::path::{Path, PathBuf};
-use std::process::{Command, exit};
-use std::fs;
+use std::process::{self, Command, Stdio, exit};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::fs::PermissionsExt;
 
 const HELP: &str = r#"
 Extract - Universal archive extractor
 
 Usage:
     extract [OPTIONS] <archive> [destination]
+    extract --to-stdout <archive> [entry]
 
 Options:
     -l, --list       List contents without extracting
     -f, --force      Overwrite existing files
     -q, --quiet      Suppress output except errors
     -k, --keep       Keep archive after extraction
+    -p, --preserve-permissions
+                     Restore permission bits stored in the archive
+                     (always on for tar formats)
+    --chmod <MODE>   Set every extracted file and directory to the octal
+                     MODE (e.g. 644 or 0755), replacing any permissions
+                     restored from the archive or by --preserve-permissions
+    --no-preserve-time
+                     Set modification times to the time of extraction
+    -s, --smart-dir  Extract into a directory named after the archive unless
+                     all entries already share one top-level directory
+    --flatten        Put every file straight into the destination, without
+                     its directories; clashing names get a counter
+                     (name-1.ext) and a warning
+    --size           Show the total uncompressed size and warn if it exceeds
+                     the free space at the destination
+    -n, --dry-run    Stop before extracting anything
+    --clean-on-error Remove the files and directories this run created if
+                     extraction fails (files overwritten with --force stay)
+    -m, --manifest <FILE>
+                     Write the paths of the extracted files, relative to the
+                     destination, to FILE (with --dry-run: the files that
+                     would be extracted)
+    -c, --to-stdout  Write decompressed data to stdout instead of files;
+                     archives (tar, zip, 7z, rar) need the entry to print
     -h, --help       Show this help message
 
 Supported formats:
     .zip, .tar, .tar.gz, .tgz, .tar.bz2, .tbz2,
-    .tar.xz, .txz, .tar.zst, .7z, .rar
+    .tar.xz, .txz, .tar.zst, .tar.lz, .tlz, .7z, .rar,
+    .gz, .bz2, .xz, .zst, .lz, .lzma, .Z, .lz4 (single compressed files)
+
+Metadata notes:
+    Permission bits for .zip are read from the archive's central directory
+    and only exist for entries created on Unix. .7z and .rar archives keep
+    whatever their extractor restores; --no-preserve-time is not supported
+    for them and is ignored with a warning.
 
 Examples:
     extract archive.zip
     extract -l backup.tar.gz
     extract data.7z /path/to/dest
+    extract --to-stdout server.log.gz | grep ERROR
+    extract -c bundle.zip config/app.toml
+    extract --size --dry-run big.tar.xz /mnt/data
+    extract --manifest files.txt release.tar.gz
+    extract --chmod 0755 tools.zip /opt/tools
+    extract --flatten assets.zip icons/
 "#;
 
 #[derive(Debug)]
@@ -34,6 +75,18 @@ struct Config {
     force: bool,
     quiet: bool,
     keep: bool,
+    preserve_permissions: bool,
+    // Mode set on everything extracted, with --chmod
+    chmod: Option<u32>,
+    preserve_time: bool,
+    to_stdout: bool,
+    entry: Option<String>,
+    smart_dir: bool,
+    flatten: bool,
+    show_size: bool,
+    dry_run: bool,
+    manifest: Option<PathBuf>,
+    clean_on_error: bool,
 }
 
 #[derive(Debug)]
@@ -44,8 +97,17 @@ enum ArchiveType {
     TarBz2,
     TarXz,
     TarZst,
+    TarLz,
     SevenZip,
     Rar,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+    Lzip,
+    Lzma,
+    Compress,
+    Lz4,
     Unknown,
 }
 
@@ -64,13 +126,23 @@ impl ArchiveType {
             (n, _) if n.ends_with(".tar.bz2") => ArchiveType::TarBz2,
             (n, _) if n.ends_with(".tar.xz") => ArchiveType::TarXz,
             (n, _) if n.ends_with(".tar.zst") => ArchiveType::TarZst,
+            (n, _) if n.ends_with(".tar.lz") => ArchiveType::TarLz,
             (_, "tgz") => ArchiveType::TarGz,
             (_, "tbz2") => ArchiveType::TarBz2,
             (_, "txz") => ArchiveType::TarXz,
+            (_, "tlz") => ArchiveType::TarLz,
             (_, "zip") => ArchiveType::Zip,
             (_, "tar") => ArchiveType::Tar,
             (_, "7z") => ArchiveType::SevenZip,
             (_, "rar") => ArchiveType::Rar,
+            (_, "gz") => ArchiveType::Gzip,
+            (_, "bz2") => ArchiveType::Bzip2,
+            (_, "xz") => ArchiveType::Xz,
+            (_, "zst") => ArchiveType::Zstd,
+            (_, "lz") => ArchiveType::Lzip,
+            (_, "lzma") => ArchiveType::Lzma,
+            (_, "z") => ArchiveType::Compress,
+            (_, "lz4") => ArchiveType::Lz4,
             _ => ArchiveType::Unknown,
         }
     }
@@ -83,8 +155,21 @@ impl ArchiveType {
             ArchiveType::TarBz2 => Some(("tar", vec!["-xjf"])),
             ArchiveType::TarXz => Some(("tar", vec!["-xJf"])),
             ArchiveType::TarZst => Some(("tar", vec!["--zstd", "-xf"])),
+            ArchiveType::TarLz => Some(("tar", vec!["--lzip", "-xf"])),
             ArchiveType::SevenZip => Some(("7z", vec!["x"])),
             ArchiveType::Rar => Some(("unrar", vec!["x"])),
+            ArchiveType::Gzip => Some(("gzip", vec!["-dc"])),
+            ArchiveType::Bzip2 => Some(("bzip2", vec!["-dc"])),
+            ArchiveType::Xz => Some(("xz", vec!["-dc"])),
+            ArchiveType::Zstd => Some(("zstd", vec!["-dcq"])),
+            ArchiveType::Lzip => Some(("lzip", vec!["-dc"])),
+            ArchiveType::Lzma => Some(("unlzma", vec!["-c"])),
+            // gzip reads .Z too, for systems without ncompress
+            ArchiveType::Compress if !check_command_exists("uncompress") => {
+                Some(("gzip", vec!["-dc"]))
+            }
+            ArchiveType::Compress => Some(("uncompress", vec!["-c"])),
+            ArchiveType::Lz4 => Some(("lz4", vec!["-dc"])),
             ArchiveType::Unknown => None,
         }
     }
@@ -97,11 +182,542 @@ impl ArchiveType {
             ArchiveType::TarBz2 => Some(("tar", vec!["-tjf"])),
             ArchiveType::TarXz => Some(("tar", vec!["-tJf"])),
             ArchiveType::TarZst => Some(("tar", vec!["--zstd", "-tf"])),
+            ArchiveType::TarLz => Some(("tar", vec!["--lzip", "-tf"])),
             ArchiveType::SevenZip => Some(("7z", vec!["l"])),
             ArchiveType::Rar => Some(("unrar", vec!["l"])),
+            ArchiveType::Gzip => Some(("gzip", vec!["-l"])),
+            ArchiveType::Xz => Some(("xz", vec!["-l"])),
+            ArchiveType::Zstd => Some(("zstd", vec!["-l"])),
+            ArchiveType::Lzip => Some(("lzip", vec!["-l"])),
+            ArchiveType::Bzip2
+            | ArchiveType::Lzma
+            | ArchiveType::Compress
+            | ArchiveType::Lz4
+            | ArchiveType::Unknown => None,
+        }
+    }
+
+    // Command that writes the decompressed data (or one entry) to stdout
+    fn get_stdout_command(&self) -> Option<(&'static str, Vec<&'static str>)> {
+        match self {
+            ArchiveType::Zip => Some(("unzip", vec!["-p"])),
+            ArchiveType::Tar => Some(("tar", vec!["-xOf"])),
+            ArchiveType::TarGz => Some(("tar", vec!["-xzOf"])),
+            ArchiveType::TarBz2 => Some(("tar", vec!["-xjOf"])),
+            ArchiveType::TarXz => Some(("tar", vec!["-xJOf"])),
+            ArchiveType::TarZst => Some(("tar", vec!["--zstd", "-xOf"])),
+            ArchiveType::TarLz => Some(("tar", vec!["--lzip", "-xOf"])),
+            ArchiveType::SevenZip => Some(("7z", vec!["x", "-so"])),
+            ArchiveType::Rar => Some(("unrar", vec!["p", "-inul"])),
+            ArchiveType::Gzip
+            | ArchiveType::Bzip2
+            | ArchiveType::Xz
+            | ArchiveType::Zstd
+            | ArchiveType::Lzip
+            | ArchiveType::Lzma
+            | ArchiveType::Compress
+            | ArchiveType::Lz4 => self.get_command(),
             ArchiveType::Unknown => None,
         }
     }
+
+    // Single compressed files rather than archives of many entries
+    fn is_stream(&self) -> bool {
+        matches!(self,
+            ArchiveType::Gzip | ArchiveType::Bzip2 | ArchiveType::Xz | ArchiveType::Zstd
+            | ArchiveType::Lzip | ArchiveType::Lzma | ArchiveType::Compress | ArchiveType::Lz4)
+    }
+
+    // Decompressor tar runs by itself for the less common formats
+    fn tar_filter(&self) -> Option<&'static str> {
+        match self {
+            ArchiveType::TarZst => Some("zstd"),
+            ArchiveType::TarLz => Some("lzip"),
+            _ => None,
+        }
+    }
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]])
+}
+
+fn invalid_zip(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// An entry from the zip central directory
+#[derive(Debug)]
+struct ZipEntry {
+    name: String,
+    // Unix mode, only present for entries created on a Unix host
+    mode: Option<u32>,
+    size: u64,
+}
+
+// Read the entries of a zip archive from its central directory
+fn read_zip_entries(path: &Path) -> io::Result<Vec<ZipEntry>> {
+    const EOCD_SIZE: u64 = 22;
+    const MAX_COMMENT: u64 = 65535;
+
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    if file_len < EOCD_SIZE {
+        return Err(invalid_zip("File too small to be a zip archive"));
+    }
+
+    // The end-of-central-directory record sits at the very end, before an
+    // optional comment of up to 64 KiB
+    let tail_len = file_len.min(EOCD_SIZE + MAX_COMMENT);
+    file.seek(SeekFrom::Start(file_len - tail_len))?;
+    let mut tail = vec![0u8; tail_len as usize];
+    file.read_exact(&mut tail)?;
+
+    let eocd = (0..=tail.len() - EOCD_SIZE as usize).rev()
+        .find(|&i| tail[i..i + 4] == [0x50, 0x4b, 0x05, 0x06])
+        .ok_or_else(|| invalid_zip("End of central directory not found"))?;
+
+    let entries = read_u16(&tail, eocd + 10) as usize;
+    let dir_size = read_u32(&tail, eocd + 12) as usize;
+    let dir_offset = read_u32(&tail, eocd + 16);
+    if entries == 0xffff || dir_offset == 0xffff_ffff {
+        return Err(invalid_zip("ZIP64 archives are not supported"));
+    }
+
+    file.seek(SeekFrom::Start(dir_offset as u64))?;
+    let mut dir = vec![0u8; dir_size];
+    file.read_exact(&mut dir)?;
+
+    let mut zip_entries = Vec::new();
+    let mut pos = 0;
+    for _ in 0..entries {
+        if pos + 46 > dir.len() || dir[pos..pos + 4] != [0x50, 0x4b, 0x01, 0x02] {
+            return Err(invalid_zip("Corrupt central directory"));
+        }
+
+        let host = dir[pos + 5];
+        let name_len = read_u16(&dir, pos + 28) as usize;
+        let extra_len = read_u16(&dir, pos + 30) as usize;
+        let comment_len = read_u16(&dir, pos + 32) as usize;
+        let size = read_u32(&dir, pos + 24) as u64;
+        let external = read_u32(&dir, pos + 38);
+
+        if pos + 46 + name_len > dir.len() {
+            return Err(invalid_zip("Corrupt central directory"));
+        }
+        let name = String::from_utf8_lossy(&dir[pos + 46..pos + 46 + name_len]).into_owned();
+
+        // Host 3 is Unix; the mode lives in the high half of the external attributes
+        let mode = external >> 16;
+        let mode = if host == 3 && mode & 0o7777 != 0 { Some(mode) } else { None };
+        zip_entries.push(ZipEntry { name, mode, size });
+
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+
+    Ok(zip_entries)
+}
+
+// Apply zip entry modes to the files extracted under `base`
+fn apply_zip_permissions(archive: &Path, base: &Path) -> Result<(), String> {
+    const S_IFMT: u32 = 0o170000;
+    const S_IFLNK: u32 = 0o120000;
+
+    let entries = read_zip_entries(archive)
+        .map_err(|e| format!("Failed to read permissions from archive: {}", e))?;
+
+    for entry in entries {
+        let (name, mode) = match entry.mode {
+            Some(mode) => (entry.name, mode),
+            None => continue,
+        };
+        // Never follow symlinks or step outside the destination
+        if mode & S_IFMT == S_IFLNK {
+            continue;
+        }
+        if is_unsafe_entry(&name) {
+            continue;
+        }
+
+        let target = base.join(&name);
+        if !target.exists() {
+            continue;
+        }
+        fs::set_permissions(&target, fs::Permissions::from_mode(mode & 0o7777))
+            .map_err(|e| format!("Failed to set permissions on {}: {}", target.display(), e))?;
+    }
+
+    Ok(())
+}
+
+// Set `mode` on the paths the archive created under `base`, including
+// directories that only appear as parents of entries. Files go first and
+// directories deepest first, so a mode without search permission doesn't
+// lock out the rest. Symlinks are left alone, as chmod would follow them.
+fn apply_chmod(names: &[String], base: &Path, mode: u32) -> Result<(), String> {
+    let mut files = HashSet::new();
+    let mut dirs = HashSet::new();
+    for name in names {
+        let name = name.trim_start_matches("./").trim_end_matches('/');
+        if name.is_empty() || is_unsafe_entry(name) {
+            continue;
+        }
+        let path = Path::new(name);
+        files.insert(path.to_path_buf());
+        dirs.extend(path.ancestors().skip(1)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(Path::to_path_buf));
+    }
+
+    let mut files: Vec<PathBuf> = files.difference(&dirs).cloned().collect();
+    let mut dirs: Vec<PathBuf> = dirs.into_iter().collect();
+    files.sort();
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+
+    for path in files.iter().chain(&dirs) {
+        let target = base.join(path);
+        match fs::symlink_metadata(&target) {
+            Ok(meta) if !meta.file_type().is_symlink() => {}
+            _ => continue,
+        }
+        fs::set_permissions(&target, fs::Permissions::from_mode(mode))
+            .map_err(|e| format!("Failed to set permissions on {}: {}", target.display(), e))?;
+    }
+
+    Ok(())
+}
+
+// Entry names that would land outside the destination: absolute paths
+// (also with a Windows drive or backslashes) and any ".." component
+fn is_unsafe_entry(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    name.starts_with('/') || name.starts_with('\\')
+        || (bytes.len() > 1 && bytes[1] == b':' && bytes[0].is_ascii_alphabetic())
+        || name.split(['/', '\\']).any(|component| component == "..")
+}
+
+// Names of all entries in an archive, used to look at its layout before
+// extracting
+fn list_entry_names(path: &Path, archive_type: &ArchiveType) -> Result<Vec<String>, String> {
+    // Archives the central directory reader does not handle, like ZIP64,
+    // are listed by unzip instead
+    if let ArchiveType::Zip = archive_type {
+        if let Ok(entries) = read_zip_entries(path) {
+            return Ok(entries.into_iter().map(|e| e.name).collect());
+        }
+    }
+
+    let (cmd, args): (&str, Vec<&str>) = match archive_type {
+        ArchiveType::Zip => ("unzip", vec!["-Z1"]),
+        ArchiveType::SevenZip => ("7z", vec!["l", "-slt", "-ba"]),
+        ArchiveType::Rar => ("unrar", vec!["lb"]),
+        _ => match archive_type.get_list_command() {
+            Some((cmd, args)) if cmd == "tar" => (cmd, args),
+            _ => return Err("Cannot list entries for this format".to_string()),
+        },
+    };
+
+    require_commands(cmd, archive_type)?;
+
+    let output = Command::new(cmd)
+        .args(args)
+        .arg(path)
+        .output()
+        .map_err(|e| format!("Failed to execute command: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Listing failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let names = if cmd == "7z" {
+        listing.lines()
+            .filter_map(|line| line.strip_prefix("Path = "))
+            .map(|name| name.to_string())
+            .collect()
+    } else {
+        listing.lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect()
+    };
+    Ok(names)
+}
+
+// Distinct first path components of the entries, ignoring a leading "./"
+fn top_level_entries(names: &[String]) -> Vec<String> {
+    let mut tops: Vec<String> = names.iter()
+        .filter_map(|name| {
+            name.trim_start_matches("./")
+                .split('/')
+                .next()
+                .filter(|top| !top.is_empty())
+                .map(|top| top.to_string())
+        })
+        .collect();
+    tops.sort();
+    tops.dedup();
+    tops
+}
+
+// Archive file name without its format suffix, e.g. "src.tar.gz" -> "src"
+fn archive_stem(path: &Path) -> String {
+    const SUFFIXES: [&str; 13] = [
+        ".tar.gz", ".tar.bz2", ".tar.xz", ".tar.zst", ".tar.lz",
+        ".tgz", ".tbz2", ".txz", ".tlz", ".zip", ".tar", ".7z", ".rar",
+    ];
+
+    let name = path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let lower = name.to_lowercase();
+    for suffix in SUFFIXES.iter() {
+        if lower.ends_with(suffix) && lower.len() > suffix.len() {
+            return name[..name.len() - suffix.len()].to_string();
+        }
+    }
+    name
+}
+
+// With --smart-dir, extract tarbombs into a directory named after the
+// archive and archives with a single top-level directory in place
+fn choose_smart_destination(config: &mut Config) -> Result<(), String> {
+    let archive_type = ArchiveType::from_path(&config.archive_path);
+    if archive_type.is_stream() {
+        return Ok(());
+    }
+
+    let names = list_entry_names(&config.archive_path, &archive_type)?;
+    let tops = top_level_entries(&names);
+
+    if tops.len() <= 1 {
+        if !config.quiet {
+            match tops.first() {
+                Some(top) => println!("Archive is rooted at '{}', extracting in place", top),
+                None => println!("Archive is empty, extracting in place"),
+            }
+        }
+        return Ok(());
+    }
+
+    let stem = archive_stem(&config.archive_path);
+    let dest = match config.destination {
+        Some(ref dest) => dest.join(&stem),
+        None => PathBuf::from(&stem),
+    };
+    if !config.quiet {
+        println!("Archive has {} top-level entries, extracting into '{}'",
+                 tops.len(), dest.display());
+    }
+    config.destination = Some(dest);
+    Ok(())
+}
+
+// Total uncompressed size and number of entries, from the archive's listing
+fn uncompressed_size(path: &Path, archive_type: &ArchiveType) -> Result<(u64, usize), String> {
+    if let ArchiveType::Zip = archive_type {
+        let entries = read_zip_entries(path)
+            .map_err(|e| format!("Failed to read archive directory: {}", e))?;
+        return Ok((entries.iter().map(|e| e.size).sum(), entries.len()));
+    }
+
+    let (cmd, args): (&str, Vec<&str>) = match archive_type {
+        ArchiveType::SevenZip => ("7z", vec!["l", "-slt", "-ba"]),
+        ArchiveType::Rar => ("unrar", vec!["lt"]),
+        ArchiveType::Gzip => ("gzip", vec!["-l"]),
+        ArchiveType::Xz => ("xz", vec!["--robot", "-l"]),
+        _ => match archive_type.get_list_command() {
+            Some((cmd, mut args)) if cmd == "tar" => {
+                args.insert(0, "-v");
+                (cmd, args)
+            }
+            _ => return Err("Size is not available for this format".to_string()),
+        },
+    };
+
+    require_commands(cmd, archive_type)?;
+
+    let output = Command::new(cmd)
+        .args(args)
+        .arg(path)
+        .output()
+        .map_err(|e| format!("Failed to execute command: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Listing failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let sizes: Vec<u64> = match cmd {
+        // 7z and unrar print one "Size" line per entry
+        "7z" => listing.lines()
+            .filter_map(|line| line.strip_prefix("Size = "))
+            .filter_map(|size| size.trim().parse().ok())
+            .collect(),
+        "unrar" => listing.lines()
+            .filter_map(|line| line.trim().strip_prefix("Size: "))
+            .filter_map(|size| size.trim().parse().ok())
+            .collect(),
+        // gzip: compressed, uncompressed, ratio, name
+        "gzip" => listing.lines().skip(1)
+            .filter_map(|line| line.split_whitespace().nth(1))
+            .filter_map(|size| size.parse().ok())
+            .collect(),
+        // xz --robot: the uncompressed size is the fifth column of "file" lines
+        "xz" => listing.lines()
+            .filter(|line| line.starts_with("file\t"))
+            .filter_map(|line| line.split('\t').nth(4))
+            .filter_map(|size| size.parse().ok())
+            .collect(),
+        // tar -tv: mode, owner/group, size, date, time, name
+        _ => listing.lines()
+            .filter_map(|line| line.split_whitespace().nth(2))
+            .filter_map(|size| size.parse().ok())
+            .collect(),
+    };
+    Ok((sizes.iter().sum(), sizes.len()))
+}
+
+// Space available to unprivileged users on the filesystem holding `path`,
+// which may not exist yet
+fn free_space(path: &Path) -> Option<u64> {
+    let existing = path.ancestors()
+        .find(|p| p.exists())
+        .map(|p| if p.as_os_str().is_empty() { Path::new(".") } else { p })?;
+
+    let output = Command::new("df")
+        .args(["-Pk"])
+        .arg(existing)
+        .output()
+        .ok()?;
+    // Filesystem, 1024-blocks, Used, Available, Capacity, Mounted on
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let available: u64 = listing.lines().nth(1)?.split_whitespace().nth(3)?.parse().ok()?;
+    Some(available * 1024)
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", size)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+// Print the uncompressed size and whether it fits on the destination
+fn print_size_summary(config: &Config) -> Result<(), String> {
+    let archive_type = ArchiveType::from_path(&config.archive_path);
+    let (total, entries) = uncompressed_size(&config.archive_path, &archive_type)?;
+
+    println!("Uncompressed size: {} ({} {})",
+             format_size(total), entries, if entries == 1 { "entry" } else { "entries" });
+
+    let dest = config.destination.clone().unwrap_or_else(|| PathBuf::from("."));
+    match free_space(&dest) {
+        Some(free) => {
+            println!("Free space at {}: {}", dest.display(), format_size(free));
+            if total > free {
+                eprintln!("Warning: archive needs {} more than is available",
+                          format_size(total - free));
+            }
+        }
+        None => eprintln!("Warning: Could not determine free space at {}", dest.display()),
+    }
+    Ok(())
+}
+
+// Paths the archive will create, relative to the destination. Directory
+// entries are left out where the listing marks them with a trailing '/'.
+fn planned_files(config: &Config) -> Result<Vec<String>, String> {
+    let archive_type = ArchiveType::from_path(&config.archive_path);
+    if archive_type.is_stream() {
+        let stem = config.archive_path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "output".to_string());
+        return Ok(vec![stem]);
+    }
+
+    let names = list_entry_names(&config.archive_path, &archive_type)?;
+    Ok(names.into_iter()
+        .map(|name| name.trim_start_matches("./").to_string())
+        .filter(|name| !name.is_empty() && !name.ends_with('/') && !is_unsafe_entry(name))
+        .collect())
+}
+
+// Keep the planned paths that are now files or links in the destination;
+// listings of 7z and rar do not mark directories, so this drops them too
+fn extracted_files(config: &Config, planned: Vec<String>) -> Vec<String> {
+    let base = config.destination.as_deref().unwrap_or(Path::new("."));
+    planned.into_iter()
+        .filter(|name| fs::symlink_metadata(base.join(name))
+            .map(|meta| !meta.is_dir())
+            .unwrap_or(false))
+        .collect()
+}
+
+fn write_manifest(path: &Path, files: &[String], quiet: bool) -> Result<(), String> {
+    let mut contents = files.join("\n");
+    if !files.is_empty() {
+        contents.push('\n');
+    }
+    fs::write(path, contents)
+        .map_err(|e| format!("Failed to write manifest {}: {}", path.display(), e))?;
+    if !quiet {
+        println!("Manifest of {} files written to {}", files.len(), path.display());
+    }
+    Ok(())
+}
+
+// Every path below `dir`, without following links
+fn snapshot(dir: &Path, paths: &mut HashSet<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        paths.insert(path.clone());
+        if is_dir {
+            snapshot(&path, paths);
+        }
+    }
+}
+
+// Remove what appeared in the destination since `before` was taken, deepest
+// paths first so directories are empty by the time they are removed
+fn clean_up(dest: &Path, before: Option<&HashSet<PathBuf>>, quiet: bool) {
+    let mut after = HashSet::new();
+    snapshot(dest, &mut after);
+    let mut created: Vec<&PathBuf> = match before {
+        Some(before) => after.iter().filter(|path| !before.contains(*path)).collect(),
+        None => after.iter().collect(),
+    };
+    created.sort_by_key(|path| std::cmp::Reverse(path.components().count()));
+
+    for path in &created {
+        let is_dir = fs::symlink_metadata(path).map(|m| m.is_dir()).unwrap_or(false);
+        let result = if is_dir { fs::remove_dir(path) } else { fs::remove_file(path) };
+        if let Err(e) = result {
+            eprintln!("Warning: Failed to remove {}: {}", path.display(), e);
+        }
+    }
+    // A destination that did not exist before goes as well
+    if before.is_none() {
+        fs::remove_dir(dest).ok();
+    }
+    if !quiet {
+        println!("Removed {} partially extracted entries", created.len());
+    }
 }
 
 fn check_command_exists(command: &str) -> bool {
@@ -112,8 +728,66 @@ fn check_command_exists(command: &str) -> bool {
         .unwrap_or(false)
 }
 
+// `cmd` and, when it is tar, the decompressor it will need
+fn require_commands(cmd: &str, archive_type: &ArchiveType) -> Result<(), String> {
+    let filter = if cmd == "tar" { archive_type.tar_filter() } else { None };
+    for command in Some(cmd).into_iter().chain(filter) {
+        if !check_command_exists(command) {
+            return Err(format!("Required command '{}' not found", command));
+        }
+    }
+    Ok(())
+}
+
+fn extract_to_stdout(config: &Config, archive_type: &ArchiveType) -> Result<(), String> {
+    let (cmd, base_args) = archive_type.get_stdout_command()
+        .ok_or_else(|| "Unsupported archive format".to_string())?;
+
+    if !archive_type.is_stream() && config.entry.is_none() {
+        return Err("--to-stdout needs the name of an entry for this archive format".to_string());
+    }
+
+    require_commands(cmd, archive_type)?;
+
+    let mut command = Command::new(cmd);
+    command.args(base_args).arg(&config.archive_path);
+    if let Some(ref entry) = config.entry {
+        command.arg(entry);
+    }
+
+    let output = command
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| format!("Failed to execute command: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!("Extraction failed: {}",
+            String::from_utf8_lossy(&output.stderr)));
+    }
+
+    Ok(())
+}
+
+// Where a single compressed file is decompressed to: its name minus the
+// compression suffix, inside the destination
+fn stream_output_path(config: &Config) -> PathBuf {
+    let stem = config.archive_path.file_stem()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("output"));
+    match config.destination {
+        Some(ref dest) => dest.join(stem),
+        None => stem,
+    }
+}
+
 fn extract_archive(config: &Config) -> Result<(), String> {
     let archive_type = ArchiveType::from_path(&config.archive_path);
+
+    // Nothing is written to disk, so destination and removal are skipped
+    if config.to_stdout {
+        return extract_to_stdout(config, &archive_type);
+    }
     
     match archive_type {
         ArchiveType::Unknown => {
@@ -125,11 +799,56 @@ fn extract_archive(config: &Config) -> Result<(), String> {
             } else {
                 archive_type.get_command()
             } {
-                if !check_command_exists(cmd) {
-                    return Err(format!("Required command '{}' not found", cmd));
+                require_commands(cmd, &archive_type)?;
+
+                // Zip-slip: entries that would be written outside the
+                // destination are excluded, whatever the extractor would do.
+                // GNU tar already refuses ".." members and strips a leading
+                // "/", so tar archives are only listed when --chmod needs the
+                // names, sparing a second pass through the decompressor.
+                let needs_names = match archive_type {
+                    ArchiveType::Zip | ArchiveType::SevenZip | ArchiveType::Rar => true,
+                    _ => config.chmod.is_some(),
+                };
+                let names = if config.list_only || archive_type.is_stream() || !needs_names {
+                    Vec::new()
+                } else {
+                    match list_entry_names(&config.archive_path, &archive_type) {
+                        Ok(names) => names,
+                        // unzip strips "../" and a leading "/" by itself;
+                        // should the archive be broken, it reports why
+                        Err(_) if cmd == "unzip" => {
+                            eprintln!("Warning: Could not list entries, relying on unzip to keep them inside the destination");
+                            Vec::new()
+                        }
+                        Err(e) => return Err(e),
+                    }
+                };
+                let unsafe_entries: Vec<String> = names.iter()
+                    .filter(|name| is_unsafe_entry(name))
+                    .cloned()
+                    .collect();
+                if !unsafe_entries.is_empty() {
+                    eprintln!("Warning: Skipping {} {} that would be written outside the destination:",
+                              unsafe_entries.len(),
+                              if unsafe_entries.len() == 1 { "entry" } else { "entries" });
+                    for name in &unsafe_entries {
+                        eprintln!("  {}", name);
+                    }
                 }
                 
                 let mut command = Command::new(cmd);
+
+                // tar takes the archive right after -f, so its options go first
+                if cmd == "tar" && !config.list_only {
+                    command.arg("--preserve-permissions");
+                    if !config.preserve_time {
+                        command.arg("--touch");
+                    }
+                    for name in &unsafe_entries {
+                        command.arg(format!("--exclude={}", name));
+                    }
+                }
                 command.args(base_args);
                 
                 // Add format-specific options
@@ -141,6 +860,9 @@ fn extract_archive(config: &Config) -> Result<(), String> {
                         if config.quiet {
                             command.arg("-qq");
                         }
+                        if !config.preserve_time && !config.list_only {
+                            command.arg("-DD");
+                        }
                     }
                     "7z" => {
                         if config.quiet {
@@ -149,6 +871,9 @@ fn extract_archive(config: &Config) -> Result<(), String> {
                         if config.force {
                             command.arg("-y");
                         }
+                        for name in &unsafe_entries {
+                            command.arg(format!("-x!{}", name));
+                        }
                     }
                     "unrar" => {
                         if config.force {
@@ -157,11 +882,26 @@ fn extract_archive(config: &Config) -> Result<(), String> {
                         if config.quiet {
                             command.arg("-inul");
                         }
+                        for name in &unsafe_entries {
+                            command.arg(format!("-x{}", name));
+                        }
                     }
                     _ => {}
                 }
+
+                if !config.preserve_time && !config.list_only && (cmd == "7z" || cmd == "unrar") {
+                    eprintln!("Warning: --no-preserve-time is not supported for this format");
+                }
                 
-                command.arg(&config.archive_path);
+                // The extractor runs inside the destination, where a relative
+                // archive path would no longer lead to the archive
+                let archive_path = fs::canonicalize(&config.archive_path)
+                    .map_err(|e| format!("Failed to resolve {}: {}", config.archive_path.display(), e))?;
+                command.arg(&archive_path);
+                // unzip takes its exclusions after the archive
+                if cmd == "unzip" && !unsafe_entries.is_empty() {
+                    command.arg("-x").args(&unsafe_entries);
+                }
                 
                 if !config.list_only {
                     if let Some(ref dest) = config.destination {
@@ -171,6 +911,18 @@ fn extract_archive(config: &Config) -> Result<(), String> {
                         
                         command.current_dir(dest);
                     }
+
+                    // Decompressors write to stdout; send it to the output file
+                    if archive_type.is_stream() {
+                        let target = stream_output_path(config);
+                        if target.exists() && !config.force {
+                            return Err(format!("{} already exists (use --force to overwrite)",
+                                target.display()));
+                        }
+                        let file = File::create(&target)
+                            .map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+                        command.stdout(file);
+                    }
                 }
                 
                 let output = command
@@ -185,14 +937,30 @@ fn extract_archive(config: &Config) -> Result<(), String> {
                 if !config.quiet {
                     println!("{}", String::from_utf8_lossy(&output.stdout));
                 }
-                
-                // Remove the archive unless the keep flag is set
-                if !config.keep && !config.list_only {
-                    fs::remove_file(&config.archive_path)
-                        .map_err(|e| format!("Failed to remove archive: {}", e))?;
+
+                if config.preserve_permissions && !config.list_only {
+                    if let ArchiveType::Zip = archive_type {
+                        let base = config.destination.as_deref().unwrap_or(Path::new("."));
+                        apply_zip_permissions(&config.archive_path, base)?;
+                    }
+                }
+
+                // Last, so it wins over whatever the archive restored
+                if let (Some(mode), false) = (config.chmod, config.list_only) {
+                    if archive_type.is_stream() {
+                        let target = stream_output_path(config);
+                        fs::set_permissions(&target, fs::Permissions::from_mode(mode))
+                            .map_err(|e| format!("Failed to set permissions on {}: {}",
+                                                 target.display(), e))?;
+                    } else {
+                        let base = config.destination.as_deref().unwrap_or(Path::new("."));
+                        apply_chmod(&names, base, mode)?;
+                    }
                 }
                 
                 Ok(())
+            } else if config.list_only {
+                Err("Listing is not supported for this format".to_string())
             } else {
                 Err("Internal error: command not found for archive type".to_string())
             }
@@ -200,6 +968,74 @@ fn extract_archive(config: &Config) -> Result<(), String> {
     }
 }
 
+// Files and links below `dir`, depth first
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match fs::symlink_metadata(&path) {
+                Ok(meta) if meta.is_dir() => collect_files(&path, files),
+                Ok(_) => files.push(path),
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+// "name-N.ext" for the Nth file that wanted "name.ext"
+fn numbered_name(name: &Path, counter: usize) -> PathBuf {
+    let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+    match name.extension() {
+        Some(ext) => PathBuf::from(format!("{}-{}.{}", stem, counter, ext.to_string_lossy())),
+        None => PathBuf::from(format!("{}-{}", stem, counter)),
+    }
+}
+
+// --flatten: extract into a staging directory inside the destination, so
+// that moving the files up is a cheap rename, then drop the directories.
+// Returns the names the files ended up with.
+fn extract_flattened(config: &mut Config) -> Result<Vec<String>, String> {
+    let dest = config.destination.clone().unwrap_or_else(|| PathBuf::from("."));
+    let staging = dest.join(format!(".extract-{}", process::id()));
+    fs::create_dir_all(&staging)
+        .map_err(|e| format!("Failed to create {}: {}", staging.display(), e))?;
+
+    let original = config.destination.replace(staging.clone());
+    let result = extract_archive(config).and_then(|_| {
+        let mut files = Vec::new();
+        collect_files(&staging, &mut files);
+        files.sort();
+
+        // Names given out in this run; files that were there before are
+        // only replaced with --force
+        let mut taken = HashSet::new();
+        let mut moved = Vec::new();
+        for file in files {
+            let name = PathBuf::from(file.file_name().unwrap_or_default());
+            let mut target = name.clone();
+            let mut counter = 0;
+            while taken.contains(&target)
+                || (!config.force && fs::symlink_metadata(dest.join(&target)).is_ok()) {
+                counter += 1;
+                target = numbered_name(&name, counter);
+            }
+            if counter > 0 {
+                eprintln!("Warning: {} clashes with an existing file, saved as {}",
+                          file.strip_prefix(&staging).unwrap_or(&file).display(), target.display());
+            }
+            fs::rename(&file, dest.join(&target))
+                .map_err(|e| format!("Failed to move {}: {}", target.display(), e))?;
+            moved.push(target.to_string_lossy().into_owned());
+            taken.insert(target);
+        }
+        Ok(moved)
+    });
+
+    config.destination = original;
+    fs::remove_dir_all(&staging).ok();
+    result
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     let mut config = Config {
@@ -209,6 +1045,17 @@ fn main() {
         force: false,
         quiet: false,
         keep: false,
+        preserve_permissions: false,
+        chmod: None,
+        preserve_time: true,
+        to_stdout: false,
+        entry: None,
+        smart_dir: false,
+        flatten: false,
+        show_size: false,
+        dry_run: false,
+        manifest: None,
+        clean_on_error: false,
     };
     
     let mut i = 1;
@@ -230,6 +1077,53 @@ fn main() {
             "-k" | "--keep" => {
                 config.keep = true;
             }
+            "-p" | "--preserve-permissions" => {
+                config.preserve_permissions = true;
+            }
+            "--chmod" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: --chmod requires a mode");
+                    exit(1);
+                }
+                let mode = &args[i];
+                config.chmod = match u32::from_str_radix(mode, 8) {
+                    Ok(value) if value <= 0o7777 && !mode.starts_with('+') => Some(value),
+                    _ => {
+                        eprintln!("Error: Invalid mode: {} (expected octal such as 644 or 0755)", mode);
+                        exit(1);
+                    }
+                };
+            }
+            "--no-preserve-time" => {
+                config.preserve_time = false;
+            }
+            "-c" | "--to-stdout" => {
+                config.to_stdout = true;
+            }
+            "-s" | "--smart-dir" => {
+                config.smart_dir = true;
+            }
+            "--flatten" => {
+                config.flatten = true;
+            }
+            "--size" => {
+                config.show_size = true;
+            }
+            "-n" | "--dry-run" => {
+                config.dry_run = true;
+            }
+            "--clean-on-error" => {
+                config.clean_on_error = true;
+            }
+            "-m" | "--manifest" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: --manifest requires a file");
+                    exit(1);
+                }
+                config.manifest = Some(PathBuf::from(&args[i]));
+            }
             _ => {
                 if config.archive_path.as_os_str().is_empty() {
                     config.archive_path = PathBuf::from(&args[i]);
@@ -241,6 +1135,12 @@ fn main() {
         i += 1;
     }
     
+    // With --to-stdout the second argument names an entry, not a destination
+    if config.to_stdout {
+        config.entry = config.destination.take()
+            .map(|entry| entry.to_string_lossy().into_owned());
+    }
+
     if config.archive_path.as_os_str().is_empty() {
         eprintln!("Error: No archive specified");
         eprintln!("Try 'extract --help' for more information.");
@@ -253,15 +1153,354 @@ fn main() {
         exit(1);
     }
     
-    match extract_archive(&config) {
-        Ok(_) => {
-            if !config.quiet && !config.list_only {
+    if config.smart_dir && !config.list_only && !config.to_stdout {
+        if let Err(e) = choose_smart_destination(&mut config) {
+            eprintln!("Error: {}", e);
+            exit(1);
+        }
+    }
+    
+    if config.show_size && !config.list_only && !config.to_stdout {
+        // Not knowing the size is no reason to refuse extracting
+        if let Err(e) = print_size_summary(&config) {
+            eprintln!("Warning: {}", e);
+        }
+    }
+
+    // Listed up front: the archive may be removed after extracting
+    let planned = match config.manifest {
+        Some(_) if !config.list_only && !config.to_stdout => match planned_files(&config) {
+            Ok(files) => Some(files),
+            Err(e) => {
+                eprintln!("Error: Cannot build manifest: {}", e);
+                exit(1);
+            }
+        },
+        _ => None,
+    };
+
+    if config.dry_run && !config.list_only {
+        if !config.quiet {
+            let dest = config.destination.clone().unwrap_or_else(|| PathBuf::from("."));
+            println!("Dry run: would extract {} into {}",
+                     config.archive_path.display(), dest.display());
+        }
+        if let (Some(path), Some(files)) = (config.manifest.as_ref(), planned.as_ref()) {
+            if let Err(e) = write_manifest(path, files, config.quiet) {
+                eprintln!("Error: {}", e);
+                exit(1);
+            }
+        }
+        return;
+    }
+    
+    // What the destination held before, to tell apart what this run created
+    let dest = config.destination.clone().unwrap_or_else(|| PathBuf::from("."));
+    let before = if config.clean_on_error && !config.list_only && !config.to_stdout && dest.exists() {
+        let mut paths = HashSet::new();
+        snapshot(&dest, &mut paths);
+        Some(paths)
+    } else {
+        None
+    };
+
+    let flatten = config.flatten && !config.list_only && !config.to_stdout
+        && !ArchiveType::from_path(&config.archive_path).is_stream();
+    let result = if flatten {
+        extract_flattened(&mut config).map(Some)
+    } else {
+        extract_archive(&config).map(|_| None)
+    };
+
+    match result {
+        Ok(flattened) => {
+            if !config.quiet && !config.list_only && !config.to_stdout {
                 println!("Extraction completed successfully.");
             }
+            if let (Some(path), Some(files)) = (config.manifest.as_ref(), planned) {
+                // Flattened files no longer sit where the archive put them
+                let files = flattened.unwrap_or_else(|| extracted_files(&config, files));
+                if let Err(e) = write_manifest(path, &files, config.quiet) {
+                    eprintln!("Error: {}", e);
+                    exit(1);
+                }
+            }
         }
         Err(e) => {
             eprintln!("Error: {}", e);
+            if config.clean_on_error && !config.list_only && !config.to_stdout {
+                clean_up(&dest, before.as_ref(), config.quiet);
+            }
             exit(1);
         }
     }
-}
\ No newline at end of file
+
+    // Only once every step succeeded, so a failed run never loses the archive
+    if !config.keep && !config.list_only && !config.to_stdout {
+        if let Err(e) = fs::remove_file(&config.archive_path) {
+            eprintln!("Error: Failed to remove archive: {}", e);
+            exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An empty directory of its own for each test
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("extract-test-{}-{}", process::id(), name));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // `path` as seen from the current directory, without changing it
+    fn relative_to_cwd(path: &Path) -> PathBuf {
+        let cwd = fs::canonicalize(env::current_dir().unwrap()).unwrap();
+        let path = fs::canonicalize(path).unwrap();
+        let mut relative = PathBuf::new();
+        for _ in cwd.components().skip(1) {
+            relative.push("..");
+        }
+        relative.join(path.strip_prefix("/").unwrap())
+    }
+
+    fn config_for(archive: &Path, destination: Option<&Path>) -> Config {
+        Config {
+            archive_path: archive.to_path_buf(),
+            destination: destination.map(Path::to_path_buf),
+            list_only: false,
+            force: false,
+            quiet: true,
+            keep: true,
+            preserve_permissions: false,
+            chmod: None,
+            preserve_time: true,
+            to_stdout: false,
+            entry: None,
+            smart_dir: false,
+            flatten: false,
+            show_size: false,
+            dry_run: false,
+            manifest: None,
+            clean_on_error: false,
+        }
+    }
+
+    fn tar(dir: &Path, archive: &str, files: &[&str]) {
+        for file in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, file.as_bytes()).unwrap();
+        }
+        let status = Command::new("tar").arg("-czf").arg(archive).args(files)
+            .current_dir(dir).status().unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn smart_dir_extracts_tarbomb_given_by_relative_path() {
+        let dir = scratch_dir("smart-dir");
+        tar(&dir, "bomb.tar.gz", &["a.txt", "b.txt"]);
+
+        let mut config = config_for(&relative_to_cwd(&dir.join("bomb.tar.gz")), None);
+        choose_smart_destination(&mut config).unwrap();
+        let dest = config.destination.clone().unwrap();
+        assert_eq!(dest, PathBuf::from("bomb"));
+
+        // Relative to the archive's directory rather than the current one
+        config.destination = Some(dir.join(&dest));
+        extract_archive(&config).unwrap();
+        assert!(dir.join("bomb/a.txt").is_file());
+        assert!(dir.join("bomb/b.txt").is_file());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn flatten_extracts_archive_given_by_relative_path() {
+        let dir = scratch_dir("flatten");
+        tar(&dir, "nested.tar.gz", &["one/a.txt", "two/deeper/a.txt", "two/b.txt"]);
+        let dest = dir.join("out");
+
+        let mut config = config_for(&relative_to_cwd(&dir.join("nested.tar.gz")), Some(&dest));
+        let mut moved = extract_flattened(&mut config).unwrap();
+        moved.sort();
+        assert_eq!(moved, ["a-1.txt", "a.txt", "b.txt"]);
+        assert_eq!(config.destination.as_deref(), Some(dest.as_path()));
+        for name in &moved {
+            assert!(dest.join(name).is_file());
+        }
+        assert!(!dest.join("one").exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    // CRC-32 as zip stores it
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in data {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+            }
+        }
+        !crc
+    }
+
+    // A zip of stored entries with any names, as no zip tool would write
+    // them; `bad_crc` gives every entry a wrong checksum
+    fn write_zip(path: &Path, entries: &[(&str, &[u8])], bad_crc: bool) {
+        let mut data = Vec::new();
+        let mut directory = Vec::new();
+        for &(name, contents) in entries {
+            let crc = crc32(contents) ^ if bad_crc { 1 } else { 0 };
+            let offset = data.len() as u32;
+            let mut common = Vec::new();
+            common.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0x21, 0]);
+            common.extend_from_slice(&crc.to_le_bytes());
+            common.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+            common.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+            common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            common.extend_from_slice(&[0, 0]);
+
+            data.extend_from_slice(&[0x50, 0x4b, 0x03, 0x04]);
+            data.extend_from_slice(&common);
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(contents);
+
+            directory.extend_from_slice(&[0x50, 0x4b, 0x01, 0x02, 20, 3]);
+            directory.extend_from_slice(&common);
+            directory.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+            directory.extend_from_slice(&(0o100644u32 << 16).to_le_bytes());
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let mut zip = data.clone();
+        zip.extend_from_slice(&directory);
+        zip.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0]);
+        zip.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        zip.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        zip.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&[0, 0]);
+        fs::write(path, zip).unwrap();
+    }
+
+    // Rewrite the end of a zip from write_zip as ZIP64 records, which
+    // read_zip_entries does not handle but unzip does
+    fn make_zip64(path: &Path) {
+        let mut zip = fs::read(path).unwrap();
+        let eocd = zip.len() - 22;
+        let entries = read_u16(&zip, eocd + 10) as u64;
+        let dir_size = read_u32(&zip, eocd + 12) as u64;
+        let dir_offset = read_u32(&zip, eocd + 16) as u64;
+        zip.truncate(eocd);
+
+        let record = zip.len() as u64;
+        zip.extend_from_slice(&[0x50, 0x4b, 0x06, 0x06]);
+        zip.extend_from_slice(&44u64.to_le_bytes());
+        zip.extend_from_slice(&[45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        for value in [entries, entries, dir_size, dir_offset] {
+            zip.extend_from_slice(&value.to_le_bytes());
+        }
+        zip.extend_from_slice(&[0x50, 0x4b, 0x06, 0x07, 0, 0, 0, 0]);
+        zip.extend_from_slice(&record.to_le_bytes());
+        zip.extend_from_slice(&1u32.to_le_bytes());
+        zip.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0]);
+        zip.extend_from_slice(&[0xff; 12]);
+        zip.extend_from_slice(&[0, 0]);
+        fs::write(path, zip).unwrap();
+    }
+
+    // A ustar archive with any member names
+    fn write_tar(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut tar = Vec::new();
+        for &(name, contents) in entries {
+            let mut header = [0u8; 512];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[100..108].copy_from_slice(b"0000644\0");
+            header[108..116].copy_from_slice(b"0000000\0");
+            header[116..124].copy_from_slice(b"0000000\0");
+            header[124..136].copy_from_slice(format!("{:011o}\0", contents.len()).as_bytes());
+            header[136..148].copy_from_slice(b"00000000000\0");
+            header[156] = b'0';
+            header[257..263].copy_from_slice(b"ustar\0");
+            header[263..265].copy_from_slice(b"00");
+            header[148..156].copy_from_slice(b"        ");
+            let sum: u32 = header.iter().map(|&byte| byte as u32).sum();
+            header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+            tar.extend_from_slice(&header);
+            tar.extend_from_slice(contents);
+            tar.resize(tar.len().div_ceil(512) * 512, 0);
+        }
+        tar.resize(tar.len() + 1024, 0);
+        fs::write(path, tar).unwrap();
+    }
+
+    #[test]
+    fn zip_entries_outside_destination_are_skipped() {
+        let dir = scratch_dir("zip-slip");
+        let archive = dir.join("slip.zip");
+        write_zip(&archive, &[("ok.txt", b"ok"), ("../escaped.txt", b"evil")], false);
+        let dest = dir.join("out");
+
+        extract_archive(&config_for(&archive, Some(&dest))).unwrap();
+        assert!(dest.join("ok.txt").is_file());
+        assert!(!dir.join("escaped.txt").exists());
+        assert!(!dest.join("escaped.txt").exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn zip64_archives_are_listed_by_unzip() {
+        let dir = scratch_dir("zip64");
+        let archive = dir.join("large.zip");
+        write_zip(&archive, &[("ok.txt", b"ok"), ("../escaped.txt", b"evil")], false);
+        make_zip64(&archive);
+        assert!(read_zip_entries(&archive).is_err());
+        assert_eq!(list_entry_names(&archive, &ArchiveType::Zip).unwrap(), ["ok.txt", "../escaped.txt"]);
+        let dest = dir.join("out");
+
+        extract_archive(&config_for(&archive, Some(&dest))).unwrap();
+        assert!(dest.join("ok.txt").is_file());
+        assert!(!dir.join("escaped.txt").exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn tar_entries_outside_destination_are_refused() {
+        let dir = scratch_dir("tar-slip");
+        let archive = dir.join("slip.tar");
+        write_tar(&archive, &[("ok.txt", b"ok"), ("../escaped.txt", b"evil")]);
+        let dest = dir.join("out");
+
+        // tar reports the refused member as an error
+        assert!(extract_archive(&config_for(&archive, Some(&dest))).is_err());
+        assert!(dest.join("ok.txt").is_file());
+        assert!(!dir.join("escaped.txt").exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn failed_extraction_is_cleaned_up() {
+        let dir = scratch_dir("clean-up");
+        let archive = dir.join("corrupt.zip");
+        write_zip(&archive, &[("a.txt", b"first"), ("sub/b.txt", b"second")], true);
+        let dest = dir.join("out");
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(dest.join("kept.txt"), "was here before").unwrap();
+
+        let mut before = HashSet::new();
+        snapshot(&dest, &mut before);
+        // unzip writes the entries, then fails on their checksums
+        assert!(extract_archive(&config_for(&archive, Some(&dest))).is_err());
+        assert!(dest.join("a.txt").exists());
+
+        clean_up(&dest, Some(&before), true);
+        assert!(dest.join("kept.txt").is_file());
+        assert!(!dest.join("a.txt").exists());
+        assert!(!dest.join("sub").exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
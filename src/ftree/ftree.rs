@@ -1,7 +1,12 @@
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
 const HELP: &str = r#"
 FTree - File System Tree Visualizer
@@ -16,6 +21,21 @@ Options:
     -d, --dirs-only    Show directories only
     -p, --pattern <P>  Filter by pattern (e.g., "*.rs")
     -i, --ignore <P>   Ignore pattern (e.g., "target")
+    -k, --keep-context With --pattern or a time filter, show only the
+                       matches and the directories leading to them
+    --newer-than <T>   Show only files modified within T (e.g. 30m, 12h, 7d, 2w)
+    --older-than <T>   Show only files last modified more than T ago
+    -a, --ascii        Draw branches with ASCII characters only
+                       (automatic when the locale is not UTF-8)
+    -j, --jobs <N>     Scan directories with N threads (default: number of CPUs)
+    -f, --full-path    Print the absolute path of each entry, one per line,
+                       instead of the tree
+    --du               Add total file size per extension to the summary
+    --depth-markers    Prefix each entry with its depth below the root
+    --warn-larger <S>  Mark files larger than S (e.g. 50MB, 1.5G) and list
+                       them in the summary
+    --fail-larger <S>  Like --warn-larger, but exit with status 1 when such
+                       a file is found
     --help            Show this help message
 
 Examples:
@@ -23,8 +43,33 @@ Examples:
     ftree -L 2 /path/to/dir
     ftree -s -h src/
     ftree -p "*.rs" -i "target"
+    ftree -k -p "*.rs" ~/projects
+    ftree -k --newer-than 7d ~/projects
+    ftree -f -p "*.rs" src/ | xargs wc -l
+    ftree --du -L 1 ~/Downloads
+    ftree --fail-larger 50MB -i "target"
 "#;
 
+// Characters used to draw the tree branches
+#[derive(Debug)]
+struct Branches {
+    tee: &'static str,
+    last: &'static str,
+    pipe: &'static str,
+}
+
+const UNICODE_BRANCHES: Branches = Branches {
+    tee: "├── ",
+    last: "└── ",
+    pipe: "│   ",
+};
+
+const ASCII_BRANCHES: Branches = Branches {
+    tee: "|-- ",
+    last: "`-- ",
+    pipe: "|   ",
+};
+
 #[derive(Debug)]
 struct Config {
     root: PathBuf,
@@ -34,13 +79,32 @@ struct Config {
     dirs_only: bool,
     pattern: Option<String>,
     ignore: Option<String>,
+    // Modification time bounds for files, from --newer-than/--older-than
+    modified_after: Option<SystemTime>,
+    modified_before: Option<SystemTime>,
+    branches: &'static Branches,
+    full_path: bool,
+    jobs: usize,
+    du: bool,
+    depth_markers: bool,
+    keep_context: bool,
+    // Files above this many bytes are reported, with --warn-larger/--fail-larger
+    large_threshold: Option<u64>,
+    fail_large: bool,
 }
 
 #[derive(Debug)]
 struct TreeStats {
     total_dirs: usize,
     total_files: usize,
+    total_symlinks: usize,
     total_size: u64,
+    // Deepest level an entry was printed at, the root's children being 1
+    max_depth_seen: usize,
+    // File sizes summed per extension, with --du
+    by_extension: HashMap<String, u64>,
+    // Files over the --warn-larger threshold, in display order
+    large_files: Vec<(PathBuf, u64)>,
 }
 
 impl Default for TreeStats {
@@ -48,7 +112,11 @@ impl Default for TreeStats {
         TreeStats {
             total_dirs: 0,
             total_files: 0,
+            total_symlinks: 0,
             total_size: 0,
+            max_depth_seen: 0,
+            by_extension: HashMap::new(),
+            large_files: Vec::new(),
         }
     }
 }
@@ -70,6 +138,44 @@ fn format_size(size: u64) -> String {
     }
 }
 
+// "50MB", "1.5G", "512k" or plain bytes; units are powers of 1024 like
+// the ones format_size prints
+fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let exponent = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 1,
+        "M" | "MB" | "MIB" => 2,
+        "G" | "GB" | "GIB" => 3,
+        "T" | "TB" | "TIB" => 4,
+        "P" | "PB" | "PIB" => 5,
+        _ => return None,
+    };
+    Some((number * 1024f64.powi(exponent)) as u64)
+}
+
+// "45s", "30m", "12h", "7d" or "2w"
+fn parse_age(text: &str) -> Option<Duration> {
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        _ => return None,
+    };
+    Some(Duration::from_secs(number.checked_mul(seconds)?))
+}
+
 fn matches_pattern(name: &str, pattern: &str) -> bool {
     if pattern.starts_with("*.") {
         name.ends_with(&pattern[1..])
@@ -110,82 +216,235 @@ fn should_process_file(
         }
     }
 
+    // Modification time check; files whose time can't be read are left out
+    if !is_dir && (config.modified_after.is_some() || config.modified_before.is_some()) {
+        let modified = match entry.metadata().and_then(|meta| meta.modified()) {
+            Ok(modified) => modified,
+            Err(_) => return false,
+        };
+        if config.modified_after.is_some_and(|cutoff| modified < cutoff)
+            || config.modified_before.is_some_and(|cutoff| modified > cutoff) {
+            return false;
+        }
+    }
+
     true
 }
 
-fn print_tree(
+// A scanned entry. The tree is collected first, possibly by several
+// threads, and printed afterwards so the output order never depends on
+// which thread finished first.
+#[derive(Debug)]
+struct Node {
+    path: PathBuf,
+    size: u64,
+    kind: NodeKind,
+}
+
+#[derive(Debug)]
+enum NodeKind {
+    Dir(Vec<Node>),
+    File,
+    // Target of the link, if it could be read
+    Symlink(Option<PathBuf>),
+}
+
+// Scan `path` and everything below it down to the depth limit. Idle threads
+// are handed out through `spare_threads`; without one a subdirectory is
+// scanned by the current thread. Only errors on the root are returned;
+// anything below it that can't be read is skipped with a warning, so one
+// unreadable directory doesn't cost the rest of the tree.
+fn build_tree(
     path: &Path,
-    prefix: &str,
-    last_item: bool,
     depth: usize,
     config: &Config,
-    stats: &mut TreeStats,
     is_root: bool,
-) -> io::Result<()> {
+    spare_threads: &AtomicUsize,
+) -> io::Result<Option<Node>> {
     if let Some(max_depth) = config.max_depth {
         if depth > max_depth {
-            return Ok(());
+            return Ok(None);
         }
     }
 
-    let metadata = fs::metadata(path)?;
-    let is_dir = metadata.is_dir();
+    // Links are reported as links and never followed, except for the root
+    let metadata = match if is_root { fs::metadata(path) } else { fs::symlink_metadata(path) } {
+        Ok(metadata) => metadata,
+        Err(e) if !is_root => {
+            eprintln!("Warning: Cannot read {}: {}", path.display(), e);
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    };
+
+    let kind = if metadata.file_type().is_symlink() {
+        NodeKind::Symlink(fs::read_link(path).ok())
+    } else if metadata.is_dir() {
+        let listing = match fs::read_dir(path) {
+            Ok(listing) => Some(listing),
+            Err(e) if !is_root => {
+                eprintln!("Warning: Cannot read directory {}: {}", path.display(), e);
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        let mut entries: Vec<_> = listing.into_iter()
+            .flatten()
+            .filter_map(|e| e.ok())
+            .filter(|e| {
+                let is_dir = e.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                should_process_file(e, config, is_dir)
+            })
+            .collect();
+
+        entries.sort_by_key(|e| (e.path().is_file(), e.file_name()));
+        let paths: Vec<PathBuf> = entries.iter().map(|e| e.path()).collect();
+
+        let children = thread::scope(|scope| {
+            let pending: Vec<_> = paths.iter()
+                .map(|child| {
+                    let claimed = spare_threads
+                        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                        .is_ok();
+                    if claimed {
+                        Err(scope.spawn(move || {
+                            let node = build_tree(child, depth + 1, config, false, spare_threads);
+                            spare_threads.fetch_add(1, Ordering::SeqCst);
+                            node
+                        }))
+                    } else {
+                        Ok(build_tree(child, depth + 1, config, false, spare_threads))
+                    }
+                })
+                .collect();
+
+            pending.into_iter()
+                .map(|result| match result {
+                    Ok(node) => node,
+                    Err(handle) => handle.join().unwrap_or_else(|_| {
+                        Err(io::Error::other("Directory scan panicked"))
+                    }),
+                })
+                .collect::<io::Result<Vec<Option<Node>>>>()
+        })?;
+
+        let children: Vec<Node> = children.into_iter().flatten().collect();
+        // Only directories with a match somewhere below them are context
+        if config.keep_context && !is_root && children.is_empty() {
+            return Ok(None);
+        }
+        NodeKind::Dir(children)
+    } else {
+        NodeKind::File
+    };
+
+    Ok(Some(Node {
+        path: path.to_path_buf(),
+        size: metadata.len(),
+        kind,
+    }))
+}
+
+fn print_tree(
+    node: &Node,
+    prefix: &str,
+    last_item: bool,
+    config: &Config,
+    stats: &mut TreeStats,
+    depth: usize,
+) {
+    let is_root = depth == 0;
+    let is_dir = matches!(node.kind, NodeKind::Dir(_));
+    let is_large = matches!(node.kind, NodeKind::File)
+        && config.large_threshold.is_some_and(|threshold| node.size > threshold);
 
     if !is_root {
-        let marker = if last_item { "└── " } else { "├── " };
+        stats.max_depth_seen = stats.max_depth_seen.max(depth);
+        if config.depth_markers {
+            print!("[{}] ", depth);
+        }
+    }
+
+    if !is_root && config.full_path {
+        println!("{}", node.path.display());
+    } else if !is_root {
+        let marker = if last_item { config.branches.last } else { config.branches.tee };
         print!("{}{}", prefix, marker);
         
-        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let name = node.path.file_name().unwrap_or_default().to_string_lossy();
         print!("{}", name);
 
-        if config.show_size {
+        if let NodeKind::Symlink(ref target) = node.kind {
+            match target {
+                Some(target) => print!(" -> {}", target.display()),
+                None => print!(" -> ?"),
+            }
+        } else if config.show_size {
             if is_dir {
                 print!(" [DIR]");
             } else {
-                print!(" [{}]", format_size(metadata.len()));
+                print!(" [{}]", format_size(node.size));
+            }
+        }
+        if is_large {
+            if config.show_size {
+                print!(" [LARGE]");
+            } else {
+                print!(" [LARGE: {}]", format_size(node.size));
             }
         }
         println!();
     }
 
-    if is_dir {
-        if !is_root {
-            stats.total_dirs += 1;
-        }
-
-        let mut entries: Vec<_> = fs::read_dir(path)?
-            .filter_map(|e| e.ok())
-            .filter(|e| should_process_file(e, config, e.path().is_dir()))
-            .collect();
-
-        entries.sort_by_key(|e| (e.path().is_file(), e.file_name()));
+    match node.kind {
+        NodeKind::Symlink(_) => stats.total_symlinks += 1,
+        NodeKind::Dir(ref children) => {
+            if !is_root {
+                stats.total_dirs += 1;
+            }
 
-        let total = entries.len();
-        for (index, entry) in entries.into_iter().enumerate() {
-            let new_prefix = if is_root {
-                String::new()
-            } else if last_item {
-                format!("{}    ", prefix)
-            } else {
-                format!("{}│   ", prefix)
-            };
-
-            print_tree(
-                &entry.path(),
-                &new_prefix,
-                index == total - 1,
-                depth + 1,
-                config,
-                stats,
-                false,
-            )?;
+            let total = children.len();
+            for (index, child) in children.iter().enumerate() {
+                let new_prefix = if is_root {
+                    String::new()
+                } else if last_item {
+                    format!("{}    ", prefix)
+                } else {
+                    format!("{}{}", prefix, config.branches.pipe)
+                };
+
+                print_tree(child, &new_prefix, index == total - 1, config, stats, depth + 1);
+            }
+        }
+        NodeKind::File => {
+            stats.total_files += 1;
+            stats.total_size += node.size;
+            if is_large {
+                stats.large_files.push((node.path.clone(), node.size));
+            }
+            if config.du {
+                let extension = match node.path.extension() {
+                    Some(ext) => format!(".{}", ext.to_string_lossy()),
+                    None => "(none)".to_string(),
+                };
+                *stats.by_extension.entry(extension).or_insert(0) += node.size;
+            }
         }
-    } else {
-        stats.total_files += 1;
-        stats.total_size += metadata.len();
     }
+}
 
-    Ok(())
+// The first locale variable that is set decides, as in setlocale(3).
+// Without any locale information we keep the default box-drawing characters.
+fn locale_is_utf8() -> bool {
+    for var in ["LC_ALL", "LC_CTYPE", "LANG"].iter() {
+        if let Ok(value) = env::var(var) {
+            if !value.is_empty() {
+                let value = value.to_lowercase();
+                return value.contains("utf-8") || value.contains("utf8");
+            }
+        }
+    }
+    true
 }
 
 fn main() -> io::Result<()> {
@@ -198,6 +457,16 @@ fn main() -> io::Result<()> {
         dirs_only: false,
         pattern: None,
         ignore: None,
+        modified_after: None,
+        modified_before: None,
+        branches: if locale_is_utf8() { &UNICODE_BRANCHES } else { &ASCII_BRANCHES },
+        full_path: false,
+        du: false,
+        depth_markers: false,
+        keep_context: false,
+        large_threshold: None,
+        fail_large: false,
+        jobs: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
     };
 
     let mut i = 1;
@@ -228,6 +497,56 @@ fn main() -> io::Result<()> {
                     config.pattern = Some(args[i].clone());
                 }
             }
+            "-a" | "--ascii" => {
+                config.branches = &ASCII_BRANCHES;
+            }
+            "-j" | "--jobs" => {
+                i += 1;
+                if i < args.len() {
+                    config.jobs = args[i].parse().unwrap_or(1).max(1);
+                }
+            }
+            "--du" => {
+                config.du = true;
+            }
+            "--newer-than" | "--mtime-newer" | "--older-than" | "--mtime-older" => {
+                let newer = args[i].contains("newer");
+                i += 1;
+                let cutoff = args.get(i)
+                    .and_then(|age| parse_age(age))
+                    .and_then(|age| SystemTime::now().checked_sub(age));
+                match cutoff {
+                    Some(cutoff) if newer => config.modified_after = Some(cutoff),
+                    Some(cutoff) => config.modified_before = Some(cutoff),
+                    None => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("{} needs an age such as 30m, 12h or 7d", args[i - 1]),
+                        ));
+                    }
+                }
+            }
+            "-k" | "--keep-context" => {
+                config.keep_context = true;
+            }
+            "--depth-markers" => {
+                config.depth_markers = true;
+            }
+            "--warn-larger" | "--fail-larger" => {
+                config.fail_large |= args[i] == "--fail-larger";
+                i += 1;
+                let threshold = args.get(i).and_then(|size| parse_size(size));
+                if threshold.is_none() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} needs a size such as 50MB", args[i - 1]),
+                    ));
+                }
+                config.large_threshold = threshold;
+            }
+            "-f" | "--full-path" => {
+                config.full_path = true;
+            }
             "-i" | "--ignore" => {
                 i += 1;
                 if i < args.len() {
@@ -243,6 +562,14 @@ fn main() -> io::Result<()> {
         i += 1;
     }
 
+    let time_filter = config.modified_after.is_some() || config.modified_before.is_some();
+    if config.keep_context && config.pattern.is_none() && !time_filter {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--keep-context needs a --pattern or a time filter",
+        ));
+    }
+
     if !config.root.exists() {
         return Err(io::Error::new(
             io::ErrorKind::NotFound,
@@ -251,23 +578,71 @@ fn main() -> io::Result<()> {
     }
 
     let mut stats = TreeStats::default();
+
+    // Flat listing: entry paths are built on the absolute root
+    if config.full_path {
+        config.root = fs::canonicalize(&config.root)?;
+    }
+
+    let spare_threads = AtomicUsize::new(config.jobs - 1);
+    let tree = build_tree(&config.root, 0, &config, true, &spare_threads)?;
+
+    // Nothing but the paths in flat mode
+    if config.full_path {
+        if let Some(ref tree) = tree {
+            print_tree(tree, "", true, &config, &mut stats, 0);
+        }
+        // Offenders go to stderr, so stdout stays a plain list of paths
+        if let (Some(threshold), true) = (config.large_threshold, config.fail_large) {
+            if !stats.large_files.is_empty() {
+                eprintln!("Error: {} file(s) larger than {}:",
+                          stats.large_files.len(), format_size(threshold));
+                for (path, size) in &stats.large_files {
+                    eprintln!("  {} [{}]", path.display(), format_size(*size));
+                }
+                process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
     println!("{}", config.root.display());
-    print_tree(
-        &config.root,
-        "",
-        true,
-        0,
-        &config,
-        &mut stats,
-        true,
-    )?;
+    if let Some(ref tree) = tree {
+        print_tree(tree, "", true, &config, &mut stats, 0);
+    }
 
     println!("\nSummary:");
     println!("  {} directories", stats.total_dirs);
     println!("  {} files", stats.total_files);
+    println!("  {} symlinks", stats.total_symlinks);
+    println!("  Max depth: {}", stats.max_depth_seen);
     if config.show_size {
         println!("  Total size: {}", format_size(stats.total_size));
     }
 
+    // Largest first, ties by name
+    if config.du && !stats.by_extension.is_empty() {
+        let mut extensions: Vec<(&String, &u64)> = stats.by_extension.iter().collect();
+        extensions.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        println!("\nBy extension:");
+        for (extension, size) in extensions {
+            println!("  {}: {}", extension, format_size(*size));
+        }
+    }
+
+    if let Some(threshold) = config.large_threshold {
+        if !stats.large_files.is_empty() {
+            println!("\nLarger than {}:", format_size(threshold));
+            for (path, size) in &stats.large_files {
+                println!("  {} [{}]", path.display(), format_size(*size));
+            }
+            if config.fail_large {
+                eprintln!("Error: {} file(s) larger than {}",
+                          stats.large_files.len(), format_size(threshold));
+                process::exit(1);
+            }
+        }
+    }
+
     Ok(())
 }
\ No newline at end of file
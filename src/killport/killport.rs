@@ -1,35 +1,383 @@
 use std::env;
-use std::process::{Command, exit};
-use std::collections::HashMap;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::process::{Command, Stdio, exit};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
 
 const HELP: &str = r#"
 KillPort - Kill processes using specified ports
 
 Usage:
     killport [OPTIONS] <port1> [port2 ...]
+    killport [OPTIONS] all
+    killport [OPTIONS] --pid <PID> | --proc <NAME>
+
+Ports are numbers or service names from /etc/services (e.g. http, postgresql).
+"all" matches every listening port except 22 (ssh) and those given with
+--exclude; killing them needs --yes or a confirmation at the prompt.
+--pid and --proc work the other way round: they find every port the given
+processes listen on, list them per process and signal each process once.
+Processes of another user and those running for longer than a day are
+flagged in the listing and only signaled once their PID is typed at a
+prompt, even with --yes.
 
 Options:
     -f, --force     Force kill (SIGKILL instead of SIGTERM)
+    -s, --signal <SIG>
+                    Send SIG instead of SIGTERM (name or number, e.g. HUP or 1)
+    -b, --backend <B>
+                    How to find sockets: proc, ss, netstat or lsof (default:
+                    auto, preferring /proc, then ss, then netstat)
+        --tcp       Only match TCP sockets
+        --udp       Only match UDP sockets (both by default)
+        --state <S> Which sockets to match: listen (default), established
+                    (also clients connected to the port) or all
+        --pid <PID> Find the ports PID listens on instead of taking ports
+                    (repeatable)
+        --proc <P>  Find the ports of processes whose name matches P, as
+                    for --name
+    -n, --name <P>  Only match processes whose command name contains P, or
+                    matches it as a glob with * and ? (case-insensitive)
+        --exclude-name <P>
+                    Never signal processes whose name matches P, as for
+                    --name (repeatable)
+        --exclude-pid <PID>
+                    Never signal PID (repeatable)
+        --protect <N,..>
+                    Names that are never signaled (default: sshd,systemd;
+                    an empty list turns the defaults off). PID 1 and
+                    killport itself are always protected.
+        --tree      Also signal all descendants of each process, children
+                    before their parents
+        --group     Signal the whole process group of each process instead
+                    of the single PID
+    -x, --exclude <PORT>
+                    Leave PORT alone, a number or service name (repeatable)
+    -y, --yes       Don't ask for confirmation with "all"
     -l, --list      Only list processes without killing
+    -w, --wait      After killing, wait until nothing listens on the ports
+                    (by default a port gets one second to become free)
+    -t, --timeout <T>
+                    Give up waiting after T (e.g. 5, 5s or 500ms; default: 10s)
+    -e, --escalate  With --wait, send SIGKILL to processes that survive the
+                    timeout and wait once more (implies --wait)
+        --no-verify Don't check that the ports are free after killing
+        --sudo      Signal processes of other users through "sudo kill"
+                    instead of failing with "permission denied"
+        --old-process-threshold <T>
+                    Processes running for longer than T (e.g. 90m, 12h or
+                    2d; default: 24h) need their PID typed to be signaled
+        --no-safety Signal processes of other users and old ones without
+                    asking for their PID
+    -c, --containers [MODE]
+                    What to do with processes in Docker/containerd/Podman
+                    containers, which are always marked in the listing
+                    (Linux only): show (the default) also marks host
+                    processes, stop runs "docker stop" or "podman stop"
+                    instead of signaling the PID, ignore leaves them alone
+        --stats     Show the CPU and memory use of each process
     -v, --verbose   Show detailed information
     -q, --quiet     Suppress all output except errors
+        --pids-only Print only the matched PIDs, one per line, and nothing
+                    else; exits with status 1 when nothing matches
+        --color <WHEN>
+                    Colorize output: auto (on a terminal unless NO_COLOR is
+                    set), always or never (default: auto)
     -h, --help      Show this help message
 
 Examples:
     killport 8080
     killport -f 3000 8080
     killport -l 80 443
+    killport -l http postgresql
+    killport --signal HUP 8080
+    killport --backend lsof -l 8080
+    killport -l --containers 5432
+    killport --containers stop 8080
+    killport --tcp 53
+    killport -l --state established 5432
+    killport -l --name nginx 8080
+    killport --name 'node*' 3000
+    killport --wait --timeout 30s 3000 && npm start
+    killport --escalate --timeout 5 8080
+    killport --tree -v 8000
+    killport -l --stats 3000
+    killport --sudo 80
+    killport --old-process-threshold 2h 8080
+    killport -l all
+    killport -l --pid 3142
+    killport -l --pids-only 8080 | xargs renice -n 10 -p
+    killport --proc node
+    killport all --exclude 5432 --exclude http --yes
+    killport --exclude-name postgres --exclude-pid 4242 5432
     
-Note: Requires root privileges for ports below 1024
+Note: Processes of other users can only be signaled by root (see --sudo),
+      and without root no backend can see which process owns their sockets
+
+Exit status: 0 on success, 1 on errors or when a port is still in use after
+killing, 2 when every matching process was protected or not confirmed and
+nothing was signaled
 "#;
 
+// Signals that can be requested by name or number (Linux numbering)
+const SIGNALS: [(&str, u32); 20] = [
+    ("HUP", 1), ("INT", 2), ("QUIT", 3), ("ILL", 4), ("TRAP", 5),
+    ("ABRT", 6), ("BUS", 7), ("FPE", 8), ("KILL", 9), ("USR1", 10),
+    ("SEGV", 11), ("USR2", 12), ("PIPE", 13), ("ALRM", 14), ("TERM", 15),
+    ("CHLD", 17), ("CONT", 18), ("STOP", 19), ("TSTP", 20), ("WINCH", 28),
+];
+
+// Command names that are never signaled unless --protect says otherwise
+const DEFAULT_PROTECTED: [&str; 2] = ["sshd", "systemd"];
+
+// Never matched by "all", so a cleanup can't cut off a remote session
+const ALWAYS_EXCLUDED: u16 = 22;
+
+// Used for service names when /etc/services is missing
+const WELL_KNOWN_SERVICES: [(&str, u16); 50] = [
+    ("ftp-data", 20), ("ftp", 21), ("ssh", 22), ("telnet", 23), ("smtp", 25),
+    ("domain", 53), ("dns", 53), ("bootps", 67), ("bootpc", 68), ("tftp", 69),
+    ("http", 80), ("www", 80), ("kerberos", 88), ("pop3", 110), ("sunrpc", 111),
+    ("ntp", 123), ("netbios-ns", 137), ("netbios-ssn", 139), ("imap", 143), ("snmp", 161),
+    ("ldap", 389), ("https", 443), ("microsoft-ds", 445), ("syslog", 514), ("submission", 587),
+    ("ipp", 631), ("ldaps", 636), ("rsync", 873), ("imaps", 993), ("pop3s", 995),
+    ("socks", 1080), ("openvpn", 1194), ("ms-sql-s", 1433), ("mqtt", 1883), ("nfs", 2049),
+    ("mysql", 3306), ("rdp", 3389), ("svn", 3690), ("sip", 5060), ("amqp", 5672),
+    ("postgresql", 5432), ("postgres", 5432), ("x11", 6000), ("redis", 6379), ("irc", 6667),
+    ("http-alt", 8080), ("webcache", 8080), ("mongodb", 27017), ("memcache", 11211), ("git", 9418),
+];
+
+// Service names and aliases with their port, from /etc/services or, when it
+// can't be read, the built-in table
+fn known_services() -> Vec<(String, u16)> {
+    let content = match fs::read_to_string("/etc/services") {
+        Ok(content) => content,
+        Err(_) => {
+            return WELL_KNOWN_SERVICES.iter()
+                .map(|&(name, port)| (name.to_string(), port))
+                .collect();
+        }
+    };
+
+    let mut services = Vec::new();
+    for line in content.lines() {
+        // name port/protocol [aliases...] [# comment]
+        let line = line.split('#').next().unwrap_or("");
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 2 {
+            continue;
+        }
+        let port = match fields[1].split('/').next().and_then(|p| p.parse::<u16>().ok()) {
+            Some(port) => port,
+            None => continue,
+        };
+        for name in fields.iter().take(1).chain(fields.iter().skip(2)) {
+            services.push((name.to_lowercase(), port));
+        }
+    }
+    services
+}
+
+// Edit distance, to suggest service names close to a misspelled one
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for j in 0..b.len() {
+            let substitution = diagonal + if ca == b[j] { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn resolve_service(name: &str) -> Result<u16, String> {
+    let name = name.to_lowercase();
+    let services = known_services();
+    if let Some(&(_, port)) = services.iter().find(|(service, _)| *service == name) {
+        return Ok(port);
+    }
+
+    let mut close: Vec<(usize, &str)> = services.iter()
+        .map(|(service, _)| (edit_distance(&name, service), service.as_str()))
+        .filter(|&(distance, service)| distance <= 2 || service.starts_with(&name))
+        .collect();
+    close.sort();
+    close.dedup_by(|a, b| a.1 == b.1);
+    let suggestions: Vec<&str> = close.iter().take(5).map(|&(_, service)| service).collect();
+    if suggestions.is_empty() {
+        Err(format!("Unknown service: {}", name))
+    } else {
+        Err(format!("Unknown service: {} (did you mean {}?)", name, suggestions.join(", ")))
+    }
+}
+
+// "http (80)" for ports given by service name, otherwise the number
+fn port_label(port: u16, config: &Config) -> String {
+    match config.services.get(&port) {
+        Some(service) => format!("{} ({})", service, port),
+        None => port.to_string(),
+    }
+}
+
+// ANSI styling lives here so it can be switched off as a whole
+mod style {
+    pub const BOLD: &str = "\x1b[1m";
+    pub const RED: &str = "\x1b[31m";
+    pub const GREEN: &str = "\x1b[32m";
+    pub const YELLOW: &str = "\x1b[33m";
+    pub const CYAN: &str = "\x1b[36m";
+    const RESET: &str = "\x1b[0m";
+
+    pub fn paint(text: &str, code: &str, enabled: bool) -> String {
+        if enabled {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+// Where listening sockets are looked up
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Backend {
+    Auto,
+    Proc,
+    Ss,
+    Netstat,
+    Lsof,
+}
+
+impl Backend {
+    fn name(self) -> &'static str {
+        match self {
+            Backend::Auto => "auto",
+            Backend::Proc => "proc",
+            Backend::Ss => "ss",
+            Backend::Netstat => "netstat",
+            Backend::Lsof => "lsof",
+        }
+    }
+}
+
+// Which socket protocols to match; both unless --tcp or --udp is given
+#[derive(Debug, Clone, Copy)]
+struct Protocols {
+    tcp: bool,
+    udp: bool,
+}
+
+impl Protocols {
+    fn matches(self, protocol: &str) -> bool {
+        match protocol {
+            "tcp" => self.tcp,
+            "udp" => self.udp,
+            _ => false,
+        }
+    }
+
+    fn all(self) -> bool {
+        self.tcp && self.udp
+    }
+
+    fn describe(self) -> &'static str {
+        if self.tcp && !self.udp { "TCP" } else if self.udp && !self.tcp { "UDP" } else { "TCP/UDP" }
+    }
+}
+
+// How processes inside containers are handled, with --containers
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ContainerMode {
+    // Mark container processes in the listing; the default
+    Annotate,
+    // Also mark host processes as "host"
+    Show,
+    Stop,
+    Ignore,
+}
+
+// Which socket states to match, with --state
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SocketStates {
+    Listen,
+    Established,
+    All,
+}
+
+impl SocketStates {
+    fn matches(self, protocol: &str, state: &str) -> bool {
+        match self {
+            SocketStates::Listen => match protocol {
+                "tcp" => state == "LISTEN",
+                _ => state == "UNCONN",
+            },
+            SocketStates::Established => state == "ESTAB",
+            SocketStates::All => true,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Config {
     ports: Vec<u16>,
-    force: bool,
+    all: bool,
+    excluded_ports: Vec<u16>,
+    yes: bool,
+    // Processes whose ports are looked up, with --pid and --proc
+    owner_pids: Vec<u32>,
+    owner_name: Option<String>,
+    // Service names the ports were given as, for output
+    services: HashMap<u16, String>,
+    signal: &'static str,
+    backend: Backend,
+    protocols: Protocols,
+    states: SocketStates,
+    name: Option<String>,
+    exclude_names: Vec<String>,
+    exclude_pids: Vec<u32>,
+    protected_names: Vec<String>,
     list_only: bool,
+    containers: ContainerMode,
+    tree: bool,
+    group: bool,
+    wait: bool,
+    escalate: bool,
+    verify: bool,
+    sudo: bool,
+    // Processes this old or of another user need their PID confirmed
+    old_threshold: Duration,
+    safety: bool,
+    timeout: Duration,
+    stats: bool,
     verbose: bool,
     quiet: bool,
+    pids_only: bool,
+    // Colors on stdout and on stderr
+    color: bool,
+    color_err: bool,
+}
+
+impl Config {
+    fn paint(&self, text: &str, code: &str) -> String {
+        style::paint(text, code, self.color)
+    }
+
+    fn paint_err(&self, text: &str, code: &str) -> String {
+        style::paint(text, code, self.color_err)
+    }
+
+    // Ports are found from the processes rather than given
+    fn inverse(&self) -> bool {
+        !self.owner_pids.is_empty() || self.owner_name.is_some()
+    }
 }
 
 #[derive(Debug)]
@@ -38,90 +386,1217 @@ struct ProcessInfo {
     name: String,
     user: String,
     protocol: String,
+    // State as ss names it: LISTEN, UNCONN, ESTAB, TIME-WAIT, ...
     state: String,
+    // Connected to the port from elsewhere rather than serving it
+    client: bool,
+    // Local end of the socket, e.g. 127.0.0.1:8080 or [::]:8080
+    local_addr: String,
+    // CPU percent and resident memory in KiB, only looked up with --stats
+    cpu: Option<f32>,
+    rss: Option<u64>,
+}
+
+fn get_processes_by_port(port: u16, backend: Backend, protocols: Protocols,
+                         states: SocketStates) -> Vec<ProcessInfo> {
+    let processes = match backend {
+        Backend::Proc => proc_processes_by_port(port, states),
+        Backend::Lsof => lsof_processes_by_port(port),
+        Backend::Netstat => netstat_processes_by_port(port, states),
+        Backend::Ss | Backend::Auto => ss_processes_by_port(port, protocols, states),
+    };
+    // A process with many connections on the port is listed once per kind
+    let mut seen = HashSet::new();
+    processes.into_iter()
+        .filter(|proc| protocols.matches(&proc.protocol) && states.matches(&proc.protocol, &proc.state))
+        .filter(|proc| seen.insert((proc.pid, proc.protocol.clone(), proc.state.clone(), proc.client)))
+        .collect()
 }
 
-fn get_processes_by_port(port: u16) -> Vec<ProcessInfo> {
+// By PID and then protocol, so the listing of a port is reproducible
+fn sort_processes(processes: &mut [ProcessInfo]) {
+    processes.sort_by(|a, b| a.pid.cmp(&b.pid).then_with(|| a.protocol.cmp(&b.protocol)));
+}
+
+// Whether a socket belongs to the port: false when the port is its local
+// end, true when only the remote end is the port, None when neither is
+fn port_end(local: &str, peer: &str, port: u16) -> Option<bool> {
+    let suffix = format!(":{}", port);
+    if local.ends_with(&suffix) {
+        Some(false)
+    } else if peer.ends_with(&suffix) {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+// Every port something listens on, for "all"
+fn listening_ports(backend: Backend, protocols: Protocols) -> Vec<u16> {
+    let mut ports = Vec::new();
+    match backend {
+        Backend::Proc => {
+            let tables = [("tcp", "tcp", "0A"), ("tcp6", "tcp", "0A"),
+                          ("udp", "udp", "07"), ("udp6", "udp", "07")];
+            for &(table, protocol, listen_state) in &tables {
+                if !protocols.matches(protocol) {
+                    continue;
+                }
+                let content = fs::read_to_string(format!("/proc/net/{}", table)).unwrap_or_default();
+                for line in content.lines().skip(1) {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    if fields.len() < 4 || fields[3] != listen_state {
+                        continue;
+                    }
+                    if let Some(port) = fields[1].rsplit(':').next()
+                        .and_then(|hex| u16::from_str_radix(hex, 16).ok()) {
+                        ports.push(port);
+                    }
+                }
+            }
+        }
+        Backend::Ss | Backend::Auto => {
+            let mut flags = String::from("-ln");
+            if protocols.tcp {
+                flags.push('t');
+            }
+            if protocols.udp {
+                flags.push('u');
+            }
+            if let Ok(output) = Command::new("ss").arg(&flags).output() {
+                let output = String::from_utf8_lossy(&output.stdout);
+                for line in output.lines().skip(1) {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    // Local Address:Port follows Netid, State, Recv-Q and Send-Q,
+                    // and Netid is left out for a single protocol
+                    let local = if protocols.all() { fields.get(4) } else { fields.get(3) };
+                    if let Some(port) = local.and_then(|l| l.rsplit(':').next()?.parse().ok()) {
+                        ports.push(port);
+                    }
+                }
+            }
+        }
+        Backend::Netstat => {
+            let output = run_netstat(false).map(|(output, _)| output).unwrap_or_default();
+            for socket in parse_netstat(&output) {
+                let listening = match socket.protocol.as_str() {
+                    "tcp" => socket.state == "LISTEN",
+                    _ => socket.state == "UNCONN",
+                };
+                if !listening || !protocols.matches(&socket.protocol) {
+                    continue;
+                }
+                if let Some(port) = socket.local.rsplit(':').next().and_then(|p| p.parse().ok()) {
+                    ports.push(port);
+                }
+            }
+        }
+        Backend::Lsof => {
+            let mut queries = Vec::new();
+            if protocols.tcp {
+                queries.push(vec!["-nP", "-iTCP", "-sTCP:LISTEN"]);
+            }
+            if protocols.udp {
+                queries.push(vec!["-nP", "-iUDP"]);
+            }
+            for query in queries {
+                let output = match Command::new("lsof").args(&query).output() {
+                    Ok(output) => output,
+                    Err(_) => continue,
+                };
+                let output = String::from_utf8_lossy(&output.stdout);
+                for line in output.lines().skip(1) {
+                    // NAME is the ninth column; connected sockets have "->"
+                    let name = match line.split_whitespace().nth(8) {
+                        Some(name) if !name.contains("->") => name,
+                        _ => continue,
+                    };
+                    if let Some(port) = name.rsplit(':').next().and_then(|p| p.parse().ok()) {
+                        ports.push(port);
+                    }
+                }
+            }
+        }
+    }
+    ports.sort_unstable();
+    ports.dedup();
+    ports
+}
+
+// A port argument: a number or a service name
+fn parse_port(arg: &str) -> Result<u16, String> {
+    if let Ok(port) = arg.parse::<u16>() {
+        Ok(port)
+    } else if arg.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        resolve_service(arg)
+    } else {
+        Err(format!("Invalid port number: {}", arg))
+    }
+}
+
+// Ask before signaling everything "all" found; only a "y" goes ahead.
+// Prompts go to stderr, as stdout may be a pipe with --pids-only.
+fn confirm(prompt: &str) -> bool {
+    eprint!("{} [y/N] ", prompt);
+    io::stderr().flush().ok();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).is_ok()
+        && matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+// A "y" is too easy to give out of habit, so the PID itself has to be
+// typed
+fn confirm_pid(prompt: &str, pid: u32) -> bool {
+    eprint!("{} Type its PID to confirm: ", prompt);
+    io::stderr().flush().ok();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).is_ok() && answer.trim() == pid.to_string()
+}
+
+// Pick the first backend that is usable on this system
+fn detect_backend() -> Backend {
+    if Path::new("/proc/net/tcp").exists() {
+        Backend::Proc
+    } else if command_exists("ss") {
+        Backend::Ss
+    } else if command_exists("netstat") {
+        Backend::Netstat
+    } else {
+        Backend::Lsof
+    }
+}
+
+fn command_exists(cmd: &str) -> bool {
+    Command::new("which")
+        .arg(cmd)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+fn ss_processes_by_port(port: u16, protocols: Protocols, states: SocketStates) -> Vec<ProcessInfo> {
+    // Query sockets of the requested protocols only, listening ones unless
+    // other states are asked for
+    let mut flags = String::from(if states == SocketStates::Listen { "-pln" } else { "-pan" });
+    if protocols.tcp {
+        flags.push('t');
+    }
+    if protocols.udp {
+        flags.push('u');
+    }
+    match Command::new("ss").arg(&flags).output() {
+        Ok(output) => parse_ss(&String::from_utf8_lossy(&output.stdout), port, protocols),
+        Err(_) => Vec::new(),
+    }
+}
+
+// Sockets on the port from `ss -p` output
+fn parse_ss(output: &str, port: u16, protocols: Protocols) -> Vec<ProcessInfo> {
     let mut processes = Vec::new();
-    
-    // Query TCP/UDP connections
-    if let Ok(output) = Command::new("ss")
-        .args(&["-tupln"])
-        .output() {
-        
-        let output = String::from_utf8_lossy(&output.stdout);
-        
-        for line in output.lines().skip(1) { // Skip the header line
-            let fields: Vec<&str> = line.split_whitespace().collect();
-            if fields.len() >= 6 {
-                // Check whether this line contains our port
-                if fields[3].contains(&format!(":{}", port)) {
-                    // Extract PID from the last field
-                    if let Some(pid_str) = fields.last()
-                        .and_then(|s| s.split(',').find(|s| s.starts_with("pid=")))
-                        .and_then(|s| s.split('=').nth(1)) {
-                        
-                        if let Ok(pid) = pid_str.parse::<u32>() {
-                            // Get process details
-                            if let Ok(proc_output) = Command::new("ps")
-                                .args(&["-p", &pid.to_string(), "-o", "comm=,user="])
-                                .output() {
-                                
-                                let proc_info = String::from_utf8_lossy(&proc_output.stdout);
-                                let proc_fields: Vec<&str> = proc_info.split_whitespace().collect();
-                                
-                                if proc_fields.len() >= 2 {
-                                    processes.push(ProcessInfo {
-                                        pid,
-                                        name: proc_fields[0].to_string(),
-                                        user: proc_fields[1].to_string(),
-                                        protocol: fields[0].to_string(),
-                                        state: fields[1].to_string(),
-                                    });
-                                }
-                            }
+
+    for line in output.lines().skip(1) { // Skip the header line
+        let mut fields: Vec<&str> = line.split_whitespace().collect();
+        // ss leaves out the Netid column when asked for a single protocol
+        if !protocols.all() {
+            fields.insert(0, if protocols.tcp { "tcp" } else { "udp" });
+        }
+        // Netid, State, Recv-Q, Send-Q, Local Address:Port, Peer Address:Port, Process
+        if fields.len() >= 7 {
+            // Check whether this line contains our port
+            if let Some(client) = port_end(fields[4], fields[5], port) {
+                // Extract PID from the last field
+                if let Some(pid_str) = fields.last()
+                    .and_then(|s| s.split(',').find(|s| s.starts_with("pid=")))
+                    .and_then(|s| s.split('=').nth(1)) {
+                    
+                    if let Ok(pid) = pid_str.parse::<u32>() {
+                        if let Some((name, user)) = process_owner(pid) {
+                            processes.push(ProcessInfo {
+                                pid,
+                                name,
+                                user,
+                                protocol: fields[0].to_string(),
+                                state: fields[1].to_string(),
+                                client,
+                                local_addr: fields[4].to_string(),
+                                cpu: None,
+                                rss: None,
+                            });
                         }
                     }
                 }
             }
         }
     }
-    
-    processes
+
+    processes
+}
+
+// Command name and user of a process: from /proc where it exists, so that
+// no `ps` has to be started per PID, otherwise from `ps`
+fn process_owner(pid: u32) -> Option<(String, String)> {
+    if Path::new(&format!("/proc/{}/status", pid)).exists() {
+        return Some(proc_owner(pid));
+    }
+
+    // One field per call: command names may contain spaces, so a combined
+    // line can't be split reliably
+    let name = ps_field(pid, "comm")?;
+    let user = ps_field(pid, "user").unwrap_or_else(|| "?".to_string());
+    Some((name, user))
+}
+
+// A single `ps -o FIELD=` value; None when the process is gone or the
+// value is empty
+fn ps_field(pid: u32, field: &str) -> Option<String> {
+    let output = Command::new("ps")
+        .args(["-p", &pid.to_string(), "-o", &format!("{}=", field)])
+        .output()
+        .ok()?;
+    ps_value(&String::from_utf8_lossy(&output.stdout))
+}
+
+// The value `ps -o FIELD=` printed, without its padding; spaces inside it
+// are kept
+fn ps_value(output: &str) -> Option<String> {
+    let value = output.trim();
+    if value.is_empty() { None } else { Some(value.to_string()) }
+}
+
+// Socket state names for the hex codes in /proc/net/{tcp,udp}, as ss shows them
+fn proc_state_name(code: &str) -> &'static str {
+    match code {
+        "01" => "ESTAB",
+        "02" => "SYN-SENT",
+        "03" => "SYN-RECV",
+        "04" => "FIN-WAIT-1",
+        "05" => "FIN-WAIT-2",
+        "06" => "TIME-WAIT",
+        "07" => "UNCONN",
+        "08" => "CLOSE-WAIT",
+        "09" => "LAST-ACK",
+        "0A" => "LISTEN",
+        "0B" => "CLOSING",
+        _ => "UNKNOWN",
+    }
+}
+
+// Read sockets straight from /proc/net, then find their owners by looking
+// for the socket inodes among every process's file descriptors
+fn proc_processes_by_port(port: u16, states: SocketStates) -> Vec<ProcessInfo> {
+    let tables = [("tcp", "tcp"), ("tcp6", "tcp"), ("udp", "udp"), ("udp6", "udp")];
+
+    let mut sockets: HashMap<u64, (&str, &str, bool, String)> = HashMap::new();
+    for &(table, protocol) in &tables {
+        let content = match fs::read_to_string(format!("/proc/net/{}", table)) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+        for line in content.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 10 {
+                continue;
+            }
+            let state = proc_state_name(fields[3]);
+            if !states.matches(protocol, state) {
+                continue;
+            }
+            // local_address and rem_address are HEXIP:HEXPORT
+            let port_of = |address: &str| address.rsplit(':').next()
+                .and_then(|hex| u16::from_str_radix(hex, 16).ok());
+            let client = if port_of(fields[1]) == Some(port) {
+                false
+            } else if port_of(fields[2]) == Some(port) {
+                true
+            } else {
+                continue;
+            };
+            if let Ok(inode) = fields[9].parse::<u64>() {
+                if inode != 0 {
+                    sockets.insert(inode, (protocol, state, client, decode_proc_address(fields[1])));
+                }
+            }
+        }
+    }
+
+    let mut processes = Vec::new();
+    if sockets.is_empty() {
+        return processes;
+    }
+
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return processes,
+    };
+    for entry in entries.flatten() {
+        let pid = match entry.file_name().to_string_lossy().parse::<u32>() {
+            Ok(pid) => pid,
+            Err(_) => continue,
+        };
+        // Processes of other users are unreadable without root; skip them
+        let fds = match fs::read_dir(entry.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(_) => continue,
+        };
+
+        // A process holding the same socket on several fds is reported once
+        let mut seen = HashSet::new();
+        for fd in fds.flatten() {
+            let target = match fs::read_link(fd.path()) {
+                Ok(target) => target.to_string_lossy().into_owned(),
+                Err(_) => continue,
+            };
+            let inode = target.strip_prefix("socket:[")
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|inode| inode.parse::<u64>().ok());
+            if let Some((protocol, state, client, local_addr)) = inode.and_then(|inode| sockets.get(&inode)) {
+                if seen.insert(inode) {
+                    let (name, user) = proc_owner(pid);
+                    processes.push(ProcessInfo {
+                        pid,
+                        name,
+                        user,
+                        protocol: protocol.to_string(),
+                        state: state.to_string(),
+                        client: *client,
+                        local_addr: local_addr.clone(),
+                        cpu: None,
+                        rss: None,
+                    });
+                }
+            }
+        }
+    }
+
+    processes
+}
+
+// "0100007F:1F90" from /proc/net/tcp as "127.0.0.1:8080". The address is
+// stored as 32-bit words in host byte order; IPv6 ones get brackets.
+fn decode_proc_address(address: &str) -> String {
+    let (ip, port) = match address.split_once(':') {
+        Some(parts) => parts,
+        None => return address.to_string(),
+    };
+    let port = u16::from_str_radix(port, 16).unwrap_or(0);
+    let words: Vec<u32> = (0..ip.len() / 8)
+        .filter_map(|i| u32::from_str_radix(&ip[i * 8..i * 8 + 8], 16).ok())
+        .collect();
+    match words.len() {
+        1 => format!("{}:{}", Ipv4Addr::from(words[0].to_le_bytes()), port),
+        4 => {
+            let mut bytes = [0u8; 16];
+            for (i, word) in words.iter().enumerate() {
+                bytes[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+            }
+            format!("[{}]:{}", Ipv6Addr::from(bytes), port)
+        }
+        _ => address.to_string(),
+    }
+}
+
+// Which interfaces a local address is reachable on, for the listing
+fn address_scope(address: &str) -> &'static str {
+    let host = address.rsplit_once(':').map_or(address, |(host, _)| host);
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    // ss appends the interface a socket is bound to, as in 127.0.0.53%lo
+    let host = host.split('%').next().unwrap_or(host);
+    match host {
+        "*" | "0.0.0.0" | "::" => "all interfaces",
+        _ if host.starts_with("127.") || host == "::1" || host.starts_with("::ffff:127.") => "loopback only",
+        _ => "this address only",
+    }
+}
+
+// Command name and user name of a process, from /proc and /etc/passwd
+fn proc_owner(pid: u32) -> (String, String) {
+    let name = fs::read_to_string(format!("/proc/{}/comm", pid))
+        .map(|comm| comm_name(&comm))
+        .unwrap_or_else(|_| "?".to_string());
+
+    let uid = fs::read_to_string(format!("/proc/{}/status", pid)).ok()
+        .and_then(|status| status_uid(&status))
+        .unwrap_or_else(|| "?".to_string());
+
+    let user = fs::read_to_string("/etc/passwd").ok()
+        .and_then(|passwd| passwd_user(&passwd, &uid))
+        .unwrap_or(uid);
+
+    (name, user)
+}
+
+// The name in /proc/<pid>/comm. Only the newline is cut; the name itself
+// may end in a space.
+fn comm_name(comm: &str) -> String {
+    let name = comm.trim_end_matches('\n');
+    if name.is_empty() { "?".to_string() } else { name.to_string() }
+}
+
+// Real UID from the "Uid:" line of /proc/<pid>/status
+fn status_uid(status: &str) -> Option<String> {
+    status.lines()
+        .find(|line| line.starts_with("Uid:"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .map(|uid| uid.to_string())
+}
+
+// User name for a UID from /etc/passwd; None for an entry without a name,
+// so that the UID is shown instead
+fn passwd_user(passwd: &str, uid: &str) -> Option<String> {
+    passwd.lines()
+        .map(|line| line.split(':').collect::<Vec<&str>>())
+        .find(|fields| fields.len() > 2 && fields[2] == uid)
+        .map(|fields| fields[0].to_string())
+        .filter(|user| !user.is_empty())
+}
+
+fn lsof_processes_by_port(port: u16) -> Vec<ProcessInfo> {
+    // +c 0: the full command name, not just its first nine characters
+    match Command::new("lsof").args(["+c", "0", "-nP", &format!("-i:{}", port)]).output() {
+        Ok(output) => parse_lsof(&String::from_utf8_lossy(&output.stdout), port),
+        Err(_) => Vec::new(),
+    }
+}
+
+// State names as ss has them, for lsof and netstat
+fn ss_state_name(state: &str) -> String {
+    match state {
+        "ESTABLISHED" => "ESTAB".to_string(),
+        "SYN_RECV" => "SYN-RECV".to_string(),
+        state => state.replace('_', "-"),
+    }
+}
+
+// Sockets on the port from `lsof -i` output
+fn parse_lsof(output: &str, port: u16) -> Vec<ProcessInfo> {
+    let mut processes = Vec::new();
+
+    for line in output.lines().skip(1) { // Skip the header line
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // COMMAND, PID, USER, FD, TYPE, DEVICE, SIZE/OFF, NODE, NAME [(STATE)]
+        if fields.len() < 9 {
+            continue;
+        }
+        // NAME is "local" or "local->peer"
+        let mut ends = fields[8].splitn(2, "->");
+        let local = ends.next().unwrap_or("");
+        let peer = ends.next().unwrap_or("");
+        let state = match fields.get(9) {
+            Some(state) => ss_state_name(state.trim_matches(|c| c == '(' || c == ')')),
+            None if peer.is_empty() => "UNCONN".to_string(),
+            None => "ESTAB".to_string(),
+        };
+        let client = match port_end(local, peer, port) {
+            Some(client) => client,
+            None => continue,
+        };
+        if let Ok(pid) = fields[1].parse::<u32>() {
+            processes.push(ProcessInfo {
+                pid,
+                // lsof escapes spaces in command names
+                name: fields[0].replace("\\x20", " "),
+                user: fields[2].to_string(),
+                protocol: fields[7].to_lowercase(),
+                state,
+                client,
+                local_addr: local.to_string(),
+                cpu: None,
+                rss: None,
+            });
+        }
+    }
+
+    processes
+}
+
+// One socket from netstat output; pid is None where netstat has no PID
+// column or could not see the owner
+struct NetstatSocket {
+    protocol: String,
+    state: String,
+    local: String,
+    peer: String,
+    pid: Option<u32>,
+}
+
+// Run netstat on TCP and UDP sockets, listening ones only unless `all`.
+// Linux netstat lists owners with -p; BSD netstat takes -p for a protocol
+// instead, so it is run without and the second value is false.
+fn run_netstat(all: bool) -> Option<(String, bool)> {
+    let linux = Command::new("netstat").arg(if all { "-tuapn" } else { "-tulpn" }).output();
+    if let Ok(output) = linux {
+        if output.status.success() {
+            return Some((String::from_utf8_lossy(&output.stdout).into_owned(), true));
+        }
+    }
+    Command::new("netstat").arg("-an").output().ok()
+        .map(|output| (String::from_utf8_lossy(&output.stdout).into_owned(), false))
+}
+
+// BSD netstat separates the port with a dot, as in 127.0.0.1.8080 or
+// *.8080; turn that into host:port like the other backends
+fn netstat_address(address: &str) -> String {
+    match address.rsplit_once('.') {
+        Some((host, port)) if port == "*" || (!port.is_empty() && port.bytes().all(|b| b.is_ascii_digit())) => {
+            if host.contains(':') {
+                format!("[{}]:{}", host, port)
+            } else {
+                format!("{}:{}", host, port)
+            }
+        }
+        _ => address.to_string(),
+    }
+}
+
+// Sockets from `netstat -tulpn` (Linux) or `netstat -an` (BSD) output:
+// Proto, Recv-Q, Send-Q, Local Address, Foreign Address, [State], [PID/Program name]
+fn parse_netstat(output: &str) -> Vec<NetstatSocket> {
+    let mut sockets = Vec::new();
+
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // Headers and Unix sockets; tcp6 and BSD's tcp4 or tcp46 count as tcp
+        let protocol = match fields.first() {
+            Some(proto) if proto.starts_with("tcp") => "tcp",
+            Some(proto) if proto.starts_with("udp") => "udp",
+            _ => continue,
+        };
+        if fields.len() < 5 {
+            continue;
+        }
+        // UDP sockets usually have no state; program names may hold spaces
+        let mut rest = &fields[5..];
+        let state = match rest.first() {
+            Some(state) if state.starts_with(|c: char| c.is_ascii_uppercase()) && !state.contains('/') => {
+                rest = &rest[1..];
+                ss_state_name(state)
+            }
+            _ => "UNCONN".to_string(),
+        };
+        let pid = rest.first()
+            .and_then(|owner| owner.split('/').next())
+            .and_then(|pid| pid.parse().ok());
+        sockets.push(NetstatSocket {
+            protocol: protocol.to_string(),
+            state,
+            local: netstat_address(fields[3]),
+            peer: netstat_address(fields[4]),
+            pid,
+        });
+    }
+
+    sockets
+}
+
+// PIDs with a socket on the port, for netstat output without owners
+fn port_pids(port: u16, protocol: &str) -> Vec<u32> {
+    let output = if command_exists("lsof") {
+        Command::new("lsof")
+            .args(["-t", "-nP", &format!("-i{}:{}", protocol.to_uppercase(), port)])
+            .output()
+    } else {
+        // fuser prints the PIDs to stdout and the port to stderr
+        Command::new("fuser")
+            .args(["-n", protocol, &port.to_string()])
+            .stderr(Stdio::null())
+            .output()
+    };
+    output.map(|output| String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .filter_map(|pid| pid.parse().ok())
+        .collect())
+        .unwrap_or_default()
+}
+
+fn netstat_processes_by_port(port: u16, states: SocketStates) -> Vec<ProcessInfo> {
+    let (output, with_pids) = match run_netstat(states != SocketStates::Listen) {
+        Some(result) => result,
+        None => return Vec::new(),
+    };
+    let mut processes = Vec::new();
+    // Looked up once per protocol when netstat cannot tell the owners
+    let mut owners: HashMap<String, Vec<u32>> = HashMap::new();
+
+    for socket in parse_netstat(&output) {
+        let client = match port_end(&socket.local, &socket.peer, port) {
+            Some(client) => client,
+            None => continue,
+        };
+        let pids = match socket.pid {
+            Some(pid) => vec![pid],
+            None if with_pids => continue,
+            None => owners.entry(socket.protocol.clone())
+                .or_insert_with(|| port_pids(port, &socket.protocol))
+                .clone(),
+        };
+        for pid in pids {
+            if let Some((name, user)) = process_owner(pid) {
+                processes.push(ProcessInfo {
+                    pid,
+                    name,
+                    user,
+                    protocol: socket.protocol.clone(),
+                    state: socket.state.clone(),
+                    client,
+                    local_addr: socket.local.clone(),
+                    cpu: None,
+                    rss: None,
+                });
+            }
+        }
+    }
+
+    processes
+}
+
+// Resolve a signal given as "HUP", "SIGHUP", "hup" or "1" to its name
+fn parse_signal(spec: &str) -> Option<&'static str> {
+    if let Ok(number) = spec.parse::<u32>() {
+        return SIGNALS.iter().find(|&&(_, n)| n == number).map(|&(name, _)| name);
+    }
+
+    let upper = spec.to_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS.iter().find(|&&(n, _)| n == name).map(|&(name, _)| name)
+}
+
+// "5", "5s" or "500ms"
+fn parse_timeout(spec: &str) -> Option<Duration> {
+    if let Some(ms) = spec.strip_suffix("ms") {
+        return ms.parse().ok().map(Duration::from_millis);
+    }
+    let secs: f64 = spec.strip_suffix('s').unwrap_or(spec).parse().ok()?;
+    if secs.is_finite() && secs >= 0.0 {
+        Some(Duration::from_secs_f64(secs))
+    } else {
+        None
+    }
+}
+
+// A timeout, or a number of minutes, hours or days: "90m", "12h" or "2d"
+fn parse_threshold(spec: &str) -> Option<Duration> {
+    let (number, unit) = match spec.char_indices().last()? {
+        (end, 'm') => (&spec[..end], 60.0),
+        (end, 'h') => (&spec[..end], 3600.0),
+        (end, 'd') => (&spec[..end], 86400.0),
+        _ => return parse_timeout(spec),
+    };
+    let count: f64 = number.parse().ok()?;
+    if count.is_finite() && count >= 0.0 {
+        Some(Duration::from_secs_f64(count * unit))
+    } else {
+        None
+    }
+}
+
+// Poll until none of the ports has a listener; returns the ports still taken
+// when `timeout` runs out
+fn wait_for_ports(ports: &[u16], config: &Config, timeout: Duration) -> Vec<u16> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    let started = Instant::now();
+    loop {
+        let busy: Vec<u16> = ports.iter()
+            .cloned()
+            .filter(|&port| !get_processes_by_port(port, config.backend, config.protocols,
+                                                   config.states).is_empty())
+            .collect();
+        if busy.is_empty() || started.elapsed() >= timeout {
+            return busy;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// Case-insensitive match of a command name against --name: a glob when
+// the pattern has * or ?, a substring otherwise. `pattern` is lowercase.
+fn name_matches(name: &str, pattern: &str) -> bool {
+    let name = name.to_lowercase();
+    if !pattern.contains(['*', '?']) {
+        return name.contains(pattern);
+    }
+
+    let name: Vec<char> = name.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut n, mut p) = (0, 0);
+    // Position after the last '*' and the name position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            n += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p + 1, n));
+            p += 1;
+        } else if let Some((after, tried)) = star {
+            // Let the '*' swallow one more character
+            star = Some((after, tried + 1));
+            p = after;
+            n = tried + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Why a process must not be signaled, if it is protected
+fn protection(pid: u32, name: &str, config: &Config) -> Option<String> {
+    if pid == 1 {
+        return Some("PID 1".to_string());
+    }
+    if pid == std::process::id() {
+        return Some("killport itself".to_string());
+    }
+    if config.exclude_pids.contains(&pid) {
+        return Some("--exclude-pid".to_string());
+    }
+    if let Some(protected) = config.protected_names.iter().find(|p| name.eq_ignore_ascii_case(p)) {
+        return Some(format!("protected name '{}'", protected));
+    }
+    config.exclude_names.iter()
+        .find(|pattern| name_matches(name, pattern))
+        .map(|pattern| format!("--exclude-name '{}'", pattern))
+}
+
+// PID, parent PID and process group of every process, from /proc where it
+// exists and from `ps` elsewhere
+fn process_table() -> Vec<(u32, u32, u32)> {
+    if let Ok(entries) = fs::read_dir("/proc") {
+        return entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+            .filter_map(|pid| {
+                let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+                // The command name may contain spaces, so fields are counted
+                // from its closing parenthesis: state, ppid, pgrp, ...
+                let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+                Some((pid, fields.get(1)?.parse().ok()?, fields.get(2)?.parse().ok()?))
+            })
+            .collect();
+    }
+
+    let output = match Command::new("ps").args(["-A", "-o", "pid=,ppid=,pgid="]).output() {
+        Ok(output) => output,
+        Err(_) => return Vec::new(),
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<u32> = line.split_whitespace().filter_map(|f| f.parse().ok()).collect();
+            if fields.len() == 3 { Some((fields[0], fields[1], fields[2])) } else { None }
+        })
+        .collect()
+}
+
+// Full command line of a process with its arguments joined by spaces, from
+// /proc/<pid>/cmdline or else `ps`. Kernel threads have none.
+fn process_cmdline(pid: u32) -> Option<String> {
+    let cmdline = match fs::read(format!("/proc/{}/cmdline", pid)) {
+        Ok(raw) => raw.split(|&byte| byte == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect::<Vec<String>>()
+            .join(" "),
+        Err(_) => {
+            let output = Command::new("ps").args(["-p", &pid.to_string(), "-o", "args="]).output().ok()?;
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        }
+    };
+    if cmdline.is_empty() { None } else { Some(cmdline) }
+}
+
+// How long a process has been running. /proc/<pid>/stat has its start in
+// clock ticks after boot, compared with /proc/uptime; `ps` is the fallback.
+fn process_age(pid: u32) -> Option<Duration> {
+    let from_proc = || -> Option<Duration> {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        // starttime is field 22, the 20th after the command name
+        let start: u64 = stat[stat.rfind(')')? + 1..].split_whitespace().nth(19)?.parse().ok()?;
+        let uptime: f64 = fs::read_to_string("/proc/uptime").ok()?
+            .split_whitespace().next()?.parse().ok()?;
+        let started = start as f64 / clock_ticks() as f64;
+        Some(Duration::from_secs_f64((uptime - started).max(0.0)))
+    };
+    from_proc().or_else(|| {
+        let output = Command::new("ps").args(["-p", &pid.to_string(), "-o", "etimes="]).output().ok()?;
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok().map(Duration::from_secs)
+    })
+}
+
+// CPU percent and resident set size in KiB, as `ps` reports them
+fn process_stats(pid: u32) -> Option<(f32, u64)> {
+    let output = Command::new("ps").args(["-p", &pid.to_string(), "-o", "%cpu=,rss="]).output().ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    let mut fields = output.split_whitespace();
+    Some((fields.next()?.parse().ok()?, fields.next()?.parse().ok()?))
+}
+
+// "812 KiB", "12.4 MiB" or "1.2 GiB"
+fn format_rss(kib: u64) -> String {
+    if kib >= 1024 * 1024 {
+        format!("{:.1} GiB", kib as f64 / (1024.0 * 1024.0))
+    } else if kib >= 1024 {
+        format!("{:.1} MiB", kib as f64 / 1024.0)
+    } else {
+        format!("{} KiB", kib)
+    }
+}
+
+// ", CPU: 0.3%, Mem: 12.4 MiB" when --stats found them
+fn stats_details(proc: &ProcessInfo) -> String {
+    let mut details = String::new();
+    if let Some(cpu) = proc.cpu {
+        details.push_str(&format!(", CPU: {:.1}%", cpu));
+    }
+    if let Some(rss) = proc.rss {
+        details.push_str(&format!(", Mem: {}", format_rss(rss)));
+    }
+    details
+}
+
+// Clock ticks per second (USER_HZ), nearly always 100
+fn clock_ticks() -> u64 {
+    static TICKS: OnceLock<u64> = OnceLock::new();
+    *TICKS.get_or_init(|| {
+        Command::new("getconf").arg("CLK_TCK").output().ok()
+            .and_then(|output| String::from_utf8_lossy(&output.stdout).trim().parse().ok())
+            .filter(|&ticks| ticks > 0)
+            .unwrap_or(100)
+    })
+}
+
+// "45s", "5m 3s", "2h 13m" or "3d 4h"
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    let (days, hours, minutes) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .filter(|&c: &usize| c >= 40)
+        .unwrap_or(80)
+}
+
+// Descendants of `pid` with their depth below it, parents before children
+fn descendants(pid: u32, table: &[(u32, u32, u32)]) -> Vec<(u32, usize)> {
+    let mut found = Vec::new();
+    let mut stack: Vec<(u32, usize)> = vec![(pid, 0)];
+    while let Some((parent, depth)) = stack.pop() {
+        if parent != pid {
+            found.push((parent, depth));
+        }
+        let mut children: Vec<u32> = table.iter()
+            .filter(|&&(child, ppid, _)| ppid == parent && child != parent)
+            .map(|&(child, _, _)| child)
+            .collect();
+        children.sort_unstable_by(|a, b| b.cmp(a));
+        stack.extend(children.into_iter().map(|child| (child, depth + 1)));
+    }
+    found
+}
+
+fn process_name(pid: u32) -> String {
+    process_owner(pid).map(|(name, _)| name).unwrap_or_else(|| "?".to_string())
+}
+
+fn report_signal(sent: bool, what: &str, config: &Config, terminating: bool) {
+    if sent {
+        if config.quiet {
+            return;
+        }
+        let line = if terminating {
+            format!("Successfully terminated {}", what)
+        } else {
+            format!("Sent SIG{} to {}", config.signal, what)
+        };
+        println!("{}", config.paint(&line, style::GREEN));
+    } else if terminating {
+        eprintln!("{}", config.paint_err(&format!("Failed to terminate {}", what), style::RED));
+    } else {
+        eprintln!("{}", config.paint_err(&format!("Failed to send SIG{} to {}", config.signal, what),
+                                         style::RED));
+    }
+}
+
+// Signal 0 only checks that the process exists and may be signaled
+fn process_alive(pid: u32) -> bool {
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+// `kill`, or `sudo kill` for processes of other users with --sudo
+fn kill_command(sudo: bool) -> Command {
+    if sudo {
+        let mut command = Command::new("sudo");
+        command.arg("kill");
+        command
+    } else {
+        Command::new("kill")
+    }
+}
+
+// The error kill printed when it failed, so EPERM can be told apart
+fn kill_process(pid: u32, signal: &str, sudo: bool) -> Result<(), String> {
+    let output = kill_command(sudo)
+        .args(["-s", signal, &pid.to_string()])
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+fn kill_group(pgid: u32, signal: &str, sudo: bool) -> bool {
+    kill_command(sudo)
+        .args(["-s", signal, "--", &format!("-{}", pgid)])
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+// Real and saved user IDs of a process, the ones kill(2) checks against
+fn process_uids(pid: u32) -> Option<(u32, u32)> {
+    if let Ok(status) = fs::read_to_string(format!("/proc/{}/status", pid)) {
+        let ids: Vec<u32> = status.lines()
+            .find(|line| line.starts_with("Uid:"))?
+            .split_whitespace()
+            .skip(1)
+            .filter_map(|id| id.parse().ok())
+            .collect();
+        return Some((*ids.first()?, *ids.get(2)?));
+    }
+    let output = Command::new("ps").args(["-p", &pid.to_string(), "-o", "ruid=,svuid="]).output().ok()?;
+    let ids: Vec<u32> = String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .filter_map(|id| id.parse().ok())
+        .collect();
+    Some((*ids.first()?, *ids.get(1)?))
+}
+
+// Whether signaling `pid` would fail with EPERM: only root may signal
+// processes that belong to another user
+fn needs_privileges(pid: u32, uid: Option<u32>) -> bool {
+    match (uid, process_uids(pid)) {
+        (Some(0), _) | (None, _) | (_, None) => false,
+        (Some(uid), Some((real, saved))) => uid != real && uid != saved,
+    }
+}
+
+// Why signaling a process deserves a second thought: it belongs to another
+// user than the one running killport, or has been running for longer than
+// --old-process-threshold
+fn caution(proc: &ProcessInfo, uid: Option<u32>, config: &Config) -> Option<String> {
+    let real = process_uids(proc.pid).map(|(real, _)| real);
+    caution_reasons(&proc.user, real, process_age(proc.pid), uid, config.old_threshold)
+}
+
+// The decision behind caution, given the process's real UID and age
+fn caution_reasons(user: &str, real: Option<u32>, age: Option<Duration>, uid: Option<u32>,
+                   old_threshold: Duration) -> Option<String> {
+    let mut reasons = Vec::new();
+    if let (Some(uid), Some(real)) = (uid, real) {
+        if real != uid {
+            reasons.push(format!("owned by {}", user));
+        }
+    }
+    if let Some(age) = age.filter(|&age| age > old_threshold) {
+        reasons.push(format!("running for {}", format_age(age)));
+    }
+    if reasons.is_empty() { None } else { Some(reasons.join(", ")) }
+}
+
+fn permission_hint(what: &str, user: &str) -> String {
+    format!("Permission denied: {} is owned by {}; re-run with sudo or add --sudo", what, user)
+}
+
+#[derive(Debug)]
+struct Container {
+    runtime: &'static str,
+    id: String,
+}
+
+impl Container {
+    fn label(&self) -> String {
+        format!("{} {}", self.runtime, &self.id[..12])
+    }
+
+    // The command that can stop it; containerd has none killport can rely on
+    fn cli(&self) -> Option<&'static str> {
+        match self.runtime {
+            "docker" => Some("docker"),
+            "podman" => Some("podman"),
+            _ => None,
+        }
+    }
+}
+
+// Container runtime and ID of a process, from the cgroup paths in
+// /proc/<pid>/cgroup: "/docker/<id>" with cgroup v1, or
+// "/system.slice/docker-<id>.scope" with v2. None when it runs on the host
+// or the file cannot be read.
+fn container_of(pid: u32) -> Option<Container> {
+    let cgroups = fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+    for line in cgroups.lines() {
+        // hierarchy-ID:controllers:path
+        let path = match line.splitn(3, ':').nth(2) {
+            Some(path) => path,
+            None => continue,
+        };
+        for part in path.split('/') {
+            let id = part.trim_end_matches(".scope");
+            let id = id.rsplit('-').next().unwrap_or(id);
+            if id.len() != 64 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+                continue;
+            }
+            let runtime = if path.contains("docker") {
+                "docker"
+            } else if path.contains("libpod") {
+                "podman"
+            } else if path.contains("containerd") || path.contains("kubepods") {
+                "containerd"
+            } else {
+                "container"
+            };
+            return Some(Container { runtime, id: id.to_string() });
+        }
+    }
+    None
 }
 
-fn kill_process(pid: u32, force: bool) -> bool {
-    let signal = if force { "SIGKILL" } else { "SIGTERM" };
-    Command::new("kill")
-        .args(&[if force { "-9" } else { "-15" }, &pid.to_string()])
+// Stopping the container is the only way to free the port when the runtime
+// restarts whatever is killed inside it
+fn stop_container(container: &Container) -> Result<(), String> {
+    let cli = container.cli()
+        .ok_or_else(|| format!("{} containers can't be stopped by killport", container.runtime))?;
+    if !command_exists(cli) {
+        return Err(format!("Required command '{}' not found", cli));
+    }
+    let status = Command::new(cli)
+        .args(["stop", &container.id])
+        .stdout(Stdio::null())
         .status()
-        .map(|status| status.success())
-        .unwrap_or(false)
+        .map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} stop failed ({})", cli, status))
+    }
 }
 
-fn print_process_info(proc: &ProcessInfo, port: u16, verbose: bool) {
+// The socket state is part of the short form only when --state lets other
+// states than listening ones through
+fn print_process_info(proc: &ProcessInfo, port: &str, verbose: bool, show_state: bool,
+                      container: Option<&str>, caution: Option<&str>, color: bool) {
+    let role = if proc.client { ", client" } else { "" };
+    let port = style::paint(port, style::CYAN, color);
+    let name = style::paint(&proc.name, style::BOLD, color);
     if verbose {
         println!("Port {} ({}):", port, proc.protocol);
         println!("  PID:      {}", proc.pid);
-        println!("  Name:     {}", proc.name);
+        println!("  Name:     {}", name);
         println!("  User:     {}", proc.user);
-        println!("  State:    {}", proc.state);
+        if let Some(cmdline) = process_cmdline(proc.pid) {
+            // Cut to one line, leaving room for the label
+            let room = terminal_width().saturating_sub(13);
+            if cmdline.chars().count() > room {
+                let cut: String = cmdline.chars().take(room.saturating_sub(3)).collect();
+                println!("  Command:  {}...", cut);
+            } else {
+                println!("  Command:  {}", cmdline);
+            }
+        }
+        if let Some(age) = process_age(proc.pid) {
+            println!("  Started:  {} ago", format_age(age));
+        }
+        if let Some(cpu) = proc.cpu {
+            println!("  CPU:      {:.1}%", cpu);
+        }
+        if let Some(rss) = proc.rss {
+            println!("  Memory:   {} (resident)", format_rss(rss));
+        }
+        println!("  Address:  {} ({})", proc.local_addr, address_scope(&proc.local_addr));
+        println!("  State:    {}{}", proc.state,
+                 if proc.client { " (client connected to the port)" } else { "" });
+        if let Some(container) = container {
+            println!("  Container: {}", container);
+        }
+        if let Some(caution) = caution {
+            println!("  Caution:  {}", style::paint(caution, style::YELLOW, color));
+        }
         println!();
-    } else {
-        println!("Port {}: {} (PID: {}, User: {})",
-                port, proc.name, proc.pid, proc.user);
+        return;
+    }
+
+    let mut details = format!("PID: {}, User: {}{}", proc.pid, proc.user, stats_details(proc));
+    if show_state {
+        details.push_str(&format!(", State: {}{}", proc.state, role));
     }
+    if let Some(container) = container {
+        details.push_str(&format!(", Container: {}", container));
+    }
+    if let Some(caution) = caution {
+        details.push_str(&format!(", {}", style::paint(&format!("Caution: {}", caution), style::YELLOW, color)));
+    }
+    println!("Port {}: {} ({})", port, name, details);
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let mut config = Config {
         ports: Vec::new(),
-        force: false,
+        all: false,
+        excluded_ports: Vec::new(),
+        yes: false,
+        owner_pids: Vec::new(),
+        owner_name: None,
+        services: HashMap::new(),
+        signal: "TERM",
+        backend: Backend::Auto,
+        protocols: Protocols { tcp: false, udp: false },
+        states: SocketStates::Listen,
+        name: None,
+        exclude_names: Vec::new(),
+        exclude_pids: Vec::new(),
+        protected_names: DEFAULT_PROTECTED.iter().map(|name| name.to_string()).collect(),
         list_only: false,
+        containers: ContainerMode::Annotate,
+        tree: false,
+        group: false,
+        wait: false,
+        escalate: false,
+        verify: true,
+        sudo: false,
+        old_threshold: Duration::from_secs(24 * 3600),
+        safety: true,
+        timeout: Duration::from_secs(10),
+        stats: false,
         verbose: false,
         quiet: false,
+        pids_only: false,
+        color: false,
+        color_err: false,
     };
+    let mut color_choice = "auto".to_string();
     
     let mut i = 1;
     while i < args.len() {
@@ -131,85 +1606,1007 @@ fn main() {
                 exit(0);
             }
             "-f" | "--force" => {
-                config.force = true;
+                config.signal = "KILL";
+            }
+            "-s" | "--signal" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: Signal not specified");
+                    exit(1);
+                }
+                config.signal = match parse_signal(&args[i]) {
+                    Some(signal) => signal,
+                    None => {
+                        eprintln!("Error: Unknown signal: {}", args[i]);
+                        exit(1);
+                    }
+                };
+            }
+            "-b" | "--backend" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: Backend not specified");
+                    exit(1);
+                }
+                config.backend = match args[i].as_str() {
+                    "auto" => Backend::Auto,
+                    "proc" => Backend::Proc,
+                    "ss" => Backend::Ss,
+                    "netstat" => Backend::Netstat,
+                    "lsof" => Backend::Lsof,
+                    other => {
+                        eprintln!("Error: Unknown backend: {} (expected proc, ss, netstat or lsof)", other);
+                        exit(1);
+                    }
+                };
+            }
+            "--tcp" => {
+                config.protocols.tcp = true;
+            }
+            "--udp" => {
+                config.protocols.udp = true;
+            }
+            "--state" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: State not specified");
+                    exit(1);
+                }
+                config.states = match args[i].to_lowercase().as_str() {
+                    "listen" => SocketStates::Listen,
+                    "established" => SocketStates::Established,
+                    "all" => SocketStates::All,
+                    other => {
+                        eprintln!("Error: Unknown state: {} (expected listen, established or all)", other);
+                        exit(1);
+                    }
+                };
+            }
+            "--pid" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: PID not specified");
+                    exit(1);
+                }
+                match args[i].parse::<u32>() {
+                    Ok(pid) => config.owner_pids.push(pid),
+                    Err(_) => {
+                        eprintln!("Error: Invalid PID: {}", args[i]);
+                        exit(1);
+                    }
+                }
+            }
+            "--proc" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: Process name not specified");
+                    exit(1);
+                }
+                config.owner_name = Some(args[i].to_lowercase());
+            }
+            "-n" | "--name" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: Name not specified");
+                    exit(1);
+                }
+                config.name = Some(args[i].to_lowercase());
+            }
+            "--exclude-name" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: Name not specified");
+                    exit(1);
+                }
+                config.exclude_names.push(args[i].to_lowercase());
+            }
+            "--exclude-pid" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: PID not specified");
+                    exit(1);
+                }
+                match args[i].parse::<u32>() {
+                    Ok(pid) => config.exclude_pids.push(pid),
+                    Err(_) => {
+                        eprintln!("Error: Invalid PID: {}", args[i]);
+                        exit(1);
+                    }
+                }
+            }
+            "--protect" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: Protected names not specified");
+                    exit(1);
+                }
+                config.protected_names = args[i].split(',')
+                    .map(|name| name.trim().to_lowercase())
+                    .filter(|name| !name.is_empty())
+                    .collect();
             }
             "-l" | "--list" => {
                 config.list_only = true;
             }
+            "-w" | "--wait" => {
+                config.wait = true;
+            }
+            "--no-verify" => {
+                config.verify = false;
+            }
+            "--sudo" => {
+                config.sudo = true;
+            }
+            "--old-process-threshold" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: Threshold not specified");
+                    exit(1);
+                }
+                config.old_threshold = match parse_threshold(&args[i]) {
+                    Some(threshold) => threshold,
+                    None => {
+                        eprintln!("Error: Invalid threshold: {}", args[i]);
+                        exit(1);
+                    }
+                };
+            }
+            "--no-safety" => {
+                config.safety = false;
+            }
+            "--stats" => {
+                config.stats = true;
+            }
+            "-e" | "--escalate" => {
+                config.wait = true;
+                config.escalate = true;
+            }
+            "-t" | "--timeout" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: Timeout not specified");
+                    exit(1);
+                }
+                config.timeout = match parse_timeout(&args[i]) {
+                    Some(timeout) => timeout,
+                    None => {
+                        eprintln!("Error: Invalid timeout: {}", args[i]);
+                        exit(1);
+                    }
+                };
+            }
+            "--tree" => {
+                config.tree = true;
+            }
+            "--group" => {
+                config.group = true;
+            }
+            "-c" | "--containers" => {
+                // The mode is optional, so only these words are taken as one
+                let mode = match args.get(i + 1).map(|arg| arg.as_str()) {
+                    Some("show") => Some(ContainerMode::Show),
+                    Some("stop") => Some(ContainerMode::Stop),
+                    Some("ignore") => Some(ContainerMode::Ignore),
+                    _ => None,
+                };
+                if mode.is_some() {
+                    i += 1;
+                }
+                config.containers = mode.unwrap_or(ContainerMode::Show);
+            }
             "-v" | "--verbose" => {
                 config.verbose = true;
             }
             "-q" | "--quiet" => {
                 config.quiet = true;
             }
-            _ => {
-                if let Ok(port) = args[i].parse::<u16>() {
-                    config.ports.push(port);
-                } else {
-                    eprintln!("Error: Invalid port number: {}", args[i]);
+            "--pids-only" => {
+                config.pids_only = true;
+                config.quiet = true;
+            }
+            "--color" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: Color mode not specified");
                     exit(1);
                 }
+                if !["auto", "always", "never"].contains(&args[i].as_str()) {
+                    eprintln!("Error: Invalid color mode: {} (expected auto, always or never)", args[i]);
+                    exit(1);
+                }
+                color_choice = args[i].clone();
+            }
+            "-x" | "--exclude" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: Port to exclude not specified");
+                    exit(1);
+                }
+                match parse_port(&args[i]) {
+                    Ok(port) => config.excluded_ports.push(port),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        exit(1);
+                    }
+                }
+            }
+            "-y" | "--yes" => {
+                config.yes = true;
+            }
+            "all" => {
+                config.all = true;
+            }
+            _ => {
+                match parse_port(&args[i]) {
+                    Ok(port) => {
+                        config.ports.push(port);
+                        if args[i].parse::<u16>().is_err() {
+                            config.services.insert(port, args[i].to_lowercase());
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        exit(1);
+                    }
+                }
             }
         }
         i += 1;
     }
     
-    if config.ports.is_empty() {
+    // PIDs for a pipeline never get escape codes
+    let auto = env::var_os("NO_COLOR").is_none();
+    config.color = !config.pids_only && match color_choice.as_str() {
+        "always" => true,
+        "never" => false,
+        _ => auto && io::stdout().is_terminal(),
+    };
+    config.color_err = !config.pids_only && match color_choice.as_str() {
+        "always" => true,
+        "never" => false,
+        _ => auto && io::stderr().is_terminal(),
+    };
+
+    if !config.verify && config.wait {
+        eprintln!("Error: --no-verify cannot be combined with --wait or --escalate");
+        exit(1);
+    }
+
+    if config.tree && config.group {
+        eprintln!("Error: --tree and --group cannot be combined");
+        exit(1);
+    }
+
+    if config.all && !config.ports.is_empty() {
+        eprintln!("Error: \"all\" cannot be combined with port numbers");
+        exit(1);
+    }
+    if config.inverse() && (config.all || !config.ports.is_empty()) {
+        eprintln!("Error: --pid and --proc cannot be combined with ports or \"all\"");
+        exit(1);
+    }
+    if config.ports.is_empty() && !config.all && !config.inverse() {
         eprintln!("Error: No ports specified");
         eprintln!("Try 'killport --help' for more information.");
         exit(1);
     }
     
-    // Check root privileges for ports below 1024
-    let is_root = Command::new("id")
+    if !config.protocols.tcp && !config.protocols.udp {
+        config.protocols = Protocols { tcp: true, udp: true };
+    }
+
+    let detected = config.backend == Backend::Auto;
+    if detected {
+        config.backend = detect_backend();
+    }
+    if config.verbose && !config.quiet {
+        println!("Using the {} backend{}", config.backend.name(),
+                 if detected { " (detected)" } else { "" });
+        if config.backend == Backend::Netstat && run_netstat(false).is_some_and(|(_, with_pids)| !with_pids) {
+            println!("netstat lists no owners; PIDs come from {}",
+                     if command_exists("lsof") { "lsof" } else { "fuser" });
+        }
+        println!("Matching {} sockets", config.protocols.describe());
+    }
+    let required = match config.backend {
+        Backend::Ss => Some("ss"),
+        Backend::Netstat => Some("netstat"),
+        Backend::Lsof => Some("lsof"),
+        _ => None,
+    };
+    if let Some(cmd) = required {
+        if !command_exists(cmd) {
+            eprintln!("Error: Required command '{}' not found", cmd);
+            exit(1);
+        }
+    }
+
+    if config.all {
+        config.ports = listening_ports(config.backend, config.protocols);
+        config.excluded_ports.push(ALWAYS_EXCLUDED);
+    } else if config.inverse() {
+        config.ports = listening_ports(config.backend, config.protocols);
+    }
+    let excluded = config.excluded_ports.clone();
+    config.ports.retain(|port| !excluded.contains(port));
+    if config.all && config.verbose && !config.quiet {
+        let ports: Vec<String> = config.ports.iter().map(|port| port.to_string()).collect();
+        println!("Listening ports: {}", if ports.is_empty() { "none".to_string() } else { ports.join(", ") });
+    }
+    
+    // Permission is checked per process before signaling it
+    let uid: Option<u32> = Command::new("id")
         .arg("-u")
         .output()
-        .map(|output| String::from_utf8_lossy(&output.stdout).trim() == "0")
-        .unwrap_or(false);
-    
-    let needs_root = config.ports.iter().any(|&p| p < 1024);
-    if needs_root && !is_root && !config.list_only {
-        eprintln!("Error: Root privileges required for ports below 1024");
+        .ok()
+        .and_then(|output| String::from_utf8_lossy(&output.stdout).trim().parse().ok());
+    let is_root = uid == Some(0);
+    if config.sudo && !is_root && !config.list_only && !command_exists("sudo") {
+        eprintln!("Error: Required command 'sudo' not found");
         exit(1);
     }
     
-    let mut port_processes = HashMap::new();
+    // Ordered by port, and by PID within a port, so output is reproducible
+    let mut port_processes = BTreeMap::new();
     let mut found = false;
+    // Ports whose processes were all left out by --name, with their count
+    let mut unmatched = Vec::new();
+    // Container of each matched PID that runs in one
+    let mut containers = HashMap::new();
+    // Processes left out by --containers ignore, per port
+    let mut in_containers = Vec::new();
+    // CPU and memory of each PID, looked up once even when it holds several ports
+    let mut stats = HashMap::new();
     
     // Collect process information for each port
     for &port in &config.ports {
-        let processes = get_processes_by_port(port);
+        let mut processes = get_processes_by_port(port, config.backend, config.protocols, config.states);
+        sort_processes(&mut processes);
+        if config.inverse() {
+            processes.retain(|proc| config.owner_pids.contains(&proc.pid)
+                || config.owner_name.as_ref().is_some_and(|pattern| name_matches(&proc.name, pattern)));
+        }
+        if let Some(ref pattern) = config.name {
+            let before = processes.len();
+            processes.retain(|proc| name_matches(&proc.name, pattern));
+            if processes.is_empty() && before > 0 {
+                unmatched.push((port, before));
+            }
+        }
+        for proc in &processes {
+            if let Some(container) = container_of(proc.pid) {
+                containers.insert(proc.pid, container);
+            }
+        }
+        if config.containers == ContainerMode::Ignore {
+            let before = processes.len();
+            processes.retain(|proc| !containers.contains_key(&proc.pid));
+            if processes.len() < before {
+                in_containers.push((port, before - processes.len()));
+            }
+        }
+        if config.stats {
+            for proc in processes.iter_mut() {
+                let usage = *stats.entry(proc.pid).or_insert_with(|| process_stats(proc.pid));
+                if let Some((cpu, rss)) = usage {
+                    proc.cpu = Some(cpu);
+                    proc.rss = Some(rss);
+                }
+            }
+        }
         if !processes.is_empty() {
             found = true;
             port_processes.insert(port, processes);
         }
     }
+
+    if !config.quiet {
+        for &(port, count) in &unmatched {
+            let line = format!("{} {} on port {} did not match name '{}'",
+                               count, if count == 1 { "process" } else { "processes" },
+                               port_label(port, &config), config.name.as_deref().unwrap_or(""));
+            println!("{}", config.paint(&line, style::YELLOW));
+        }
+        for &(port, count) in &in_containers {
+            let line = format!("{} {} on port {} in containers ignored",
+                               count, if count == 1 { "process" } else { "processes" },
+                               port_label(port, &config));
+            println!("{}", config.paint(&line, style::YELLOW));
+        }
+    }
     
     if !found {
-        if !config.quiet {
-            println!("No processes found for specified ports");
+        if !config.quiet && unmatched.is_empty() && in_containers.is_empty() {
+            if config.inverse() {
+                println!("No listening ports found for the given processes");
+            } else if config.protocols.all() {
+                println!("No processes found for specified ports");
+            } else {
+                println!("No processes found for specified ports ({} only)",
+                         config.protocols.describe());
+            }
+            if !is_root {
+                println!("Note: processes of other users are only visible to root");
+            }
         }
-        exit(0);
+        // A pipeline has to be able to tell that there is nothing to work on
+        exit(if config.pids_only { 1 } else { 0 });
     }
     
+    // "all" is too broad to act on without being sure
+    if config.all && !config.list_only && !config.yes {
+        let count: usize = port_processes.values().map(|processes| processes.len()).sum();
+        let ports: Vec<String> = port_processes.keys().map(|&port| port_label(port, &config)).collect();
+        let prompt = format!("Signal {} processes on ports {}?", count, ports.join(", "));
+        if !io::stdin().is_terminal() {
+            eprintln!("Error: \"all\" needs --yes when not run interactively");
+            exit(1);
+        }
+        if !confirm(&prompt) {
+            eprintln!("Nothing was signaled");
+            exit(1);
+        }
+    }
+
+    if config.pids_only {
+        let pids: BTreeSet<u32> = port_processes.values().flatten().map(|proc| proc.pid).collect();
+        for pid in pids {
+            println!("{}", pid);
+        }
+    }
+
+    let mut matched = 0;
+    let mut succeeded = 0;
+    let mut failed = 0;
+    let mut protected = 0;
+    // Ports where at least one process was signaled
+    let mut targeted = Vec::new();
+    let terminating = config.signal == "TERM" || config.signal == "KILL";
+    // With --tree or --group, a process or group is signaled only once even
+    // when it holds several of the ports
+    let mut signaled = HashSet::new();
+    let mut signaled_groups = HashSet::new();
+    let mut stopped_containers = HashSet::new();
+    let table = if (config.tree || config.group) && !config.list_only {
+        process_table()
+    } else {
+        Vec::new()
+    };
+    let own_group = table.iter()
+        .find(|&&(pid, _, _)| pid == std::process::id())
+        .map(|&(_, _, pgid)| pgid);
+
+    // Processes of other users and old ones, flagged in the listing and
+    // confirmed by PID before they are signaled
+    let mut cautions = HashMap::new();
+    if !config.quiet || (config.safety && !config.list_only) {
+        for proc in port_processes.values().flatten() {
+            if let Some(reason) = caution(proc, uid, &config) {
+                cautions.insert(proc.pid, reason);
+            }
+        }
+    }
+    // Processes that were not confirmed, left alone like protected ones
+    let mut declined = HashSet::new();
+
+    // With --pid and --proc, each process is listed once with all its ports
+    let mut owners: BTreeMap<u32, (&ProcessInfo, Vec<String>)> = BTreeMap::new();
+    if config.inverse() {
+        for (&port, processes) in &port_processes {
+            for proc in processes {
+                owners.entry(proc.pid).or_insert((proc, Vec::new()))
+                    .1.push(format!("{} ({})", config.paint(&port_label(port, &config), style::CYAN),
+                                    proc.protocol));
+            }
+        }
+        if !config.quiet {
+            for (proc, ports) in owners.values() {
+                let caution = match cautions.get(&proc.pid) {
+                    Some(reason) => format!(", {}", config.paint(&format!("Caution: {}", reason), style::YELLOW)),
+                    None => String::new(),
+                };
+                println!("{} (PID: {}, User: {}{}{}): {} {}", config.paint(&proc.name, style::BOLD),
+                         proc.pid, proc.user, stats_details(proc), caution,
+                         if ports.len() == 1 { "port" } else { "ports" }, ports.join(", "));
+            }
+        }
+    }
+    let mut listed = HashSet::new();
+
     // Print information and/or terminate processes
     for (&port, processes) in &port_processes {
         for proc in processes {
-            if !config.quiet {
-                print_process_info(proc, port, config.verbose);
+            // A process holding several ports is counted and handled once;
+            // the verification below still needs every port it held
+            if config.inverse() && !listed.insert(proc.pid) {
+                if !config.list_only && !targeted.contains(&port) && !declined.contains(&proc.pid)
+                    && protection(proc.pid, &proc.name, &config).is_none() {
+                    targeted.push(port);
+                }
+                continue;
+            }
+            matched += 1;
+            if !config.quiet && !config.inverse() {
+                let container = match containers.get(&proc.pid) {
+                    Some(container) => Some(container.label()),
+                    None if config.containers == ContainerMode::Show => Some("host".to_string()),
+                    None => None,
+                };
+                print_process_info(proc, &port_label(port, &config), config.verbose,
+                                   config.states != SocketStates::Listen, container.as_deref(),
+                                   cautions.get(&proc.pid).map(String::as_str), config.color);
             }
             
             if !config.list_only {
-                if kill_process(proc.pid, config.force) {
+                if let Some(reason) = protection(proc.pid, &proc.name, &config) {
+                    protected += 1;
                     if !config.quiet {
-                        println!("Successfully terminated process {} (PID: {})",
-                               proc.name, proc.pid);
+                        let line = format!("Skipped process {} (PID: {}): protected ({})",
+                                           proc.name, proc.pid, reason);
+                        println!("{}", config.paint(&line, style::YELLOW));
+                    }
+                    continue;
+                }
+                // Asked even with --yes: that only covers the choice of ports
+                if let Some(reason) = cautions.get(&proc.pid).filter(|_| config.safety) {
+                    let prompt = format!("Process {} (PID: {}) is {}.", proc.name, proc.pid, reason);
+                    let confirmed = io::stdin().is_terminal() && confirm_pid(&prompt, proc.pid);
+                    if !confirmed {
+                        protected += 1;
+                        declined.insert(proc.pid);
+                        let line = if io::stdin().is_terminal() {
+                            format!("Skipped process {} (PID: {}): not confirmed", proc.name, proc.pid)
+                        } else {
+                            format!("Skipped process {} (PID: {}): {}; confirm interactively or pass --no-safety",
+                                    proc.name, proc.pid, reason)
+                        };
+                        eprintln!("{}", config.paint_err(&line, style::YELLOW));
+                        continue;
+                    }
+                }
+                if !targeted.contains(&port) {
+                    targeted.push(port);
+                }
+
+                // The runtime would restart a killed process; stop its container once
+                if config.containers == ContainerMode::Stop {
+                    if let Some(container) = containers.get(&proc.pid) {
+                        if !stopped_containers.insert(container.id.clone()) {
+                            continue;
+                        }
+                        match stop_container(container) {
+                            Ok(()) => {
+                                succeeded += 1;
+                                if !config.quiet {
+                                    let line = format!("Stopped container {} of {} (PID: {})",
+                                                       container.label(), proc.name, proc.pid);
+                                    println!("{}", config.paint(&line, style::GREEN));
+                                }
+                            }
+                            Err(e) => {
+                                failed += 1;
+                                let line = format!("Failed to stop container {} of {} (PID: {}): {}",
+                                                   container.label(), proc.name, proc.pid, e);
+                                eprintln!("{}", config.paint_err(&line, style::RED));
+                            }
+                        }
+                        continue;
+                    }
+                }
+
+                // The group of this process, unless it is killport's own or init's
+                let group = if config.group {
+                    let pgid = table.iter().find(|&&(pid, _, _)| pid == proc.pid).map(|&(_, _, g)| g);
+                    match pgid {
+                        Some(pgid) if pgid > 1 && Some(pgid) != own_group => Some(pgid),
+                        _ => {
+                            eprintln!("Warning: Not signaling the process group of PID {}, \
+                                       signaling the process alone", proc.pid);
+                            None
+                        }
                     }
                 } else {
-                    eprintln!("Failed to terminate process {} (PID: {})",
-                            proc.name, proc.pid);
+                    None
+                };
+
+                if let Some(pgid) = group {
+                    if !signaled_groups.insert(pgid) {
+                        continue;
+                    }
+                    let members = table.iter().filter(|&&(_, _, g)| g == pgid).count();
+                    if config.verbose && !config.quiet {
+                        println!("Signaling process group {} ({} processes)", pgid, members);
+                    }
+                    let sudo = config.sudo && needs_privileges(proc.pid, uid);
+                    let sent = kill_group(pgid, config.signal, sudo);
+                    if sent {
+                        succeeded += members;
+                    } else {
+                        failed += 1;
+                    }
+                    report_signal(sent, &format!("process group {} of {} (PID: {})",
+                                                 pgid, proc.name, proc.pid),
+                                  &config, terminating);
+                    continue;
+                }
+
+                // Already signaled as part of an earlier process's tree
+                if signaled.contains(&proc.pid) {
+                    continue;
+                }
+                let mut targets = if config.tree { descendants(proc.pid, &table) } else { Vec::new() };
+                targets.retain(|(pid, _)| !signaled.contains(pid));
+                if !targets.is_empty() && config.verbose && !config.quiet {
+                    println!("Process tree to signal:");
+                    println!("  {} {}", proc.pid, proc.name);
+                    for &(pid, depth) in &targets {
+                        println!("  {}{} {}", "  ".repeat(depth), pid, process_name(pid));
+                    }
+                }
+                // Leaf-first: children go before their parents
+                targets.reverse();
+                targets.push((proc.pid, 0));
+                for (pid, _) in targets {
+                    if !signaled.insert(pid) {
+                        continue;
+                    }
+                    let name = if pid == proc.pid { proc.name.clone() } else { process_name(pid) };
+                    if pid != proc.pid {
+                        if let Some(reason) = protection(pid, &name, &config) {
+                            if !config.quiet {
+                                let line = format!("Skipped process {} (PID: {}): protected ({})",
+                                                   name, pid, reason);
+                                println!("{}", config.paint(&line, style::YELLOW));
+                            }
+                            continue;
+                        }
+                    }
+                    // Clients only talked to the port; say so in case that was a surprise
+                    let kind = if pid == proc.pid && proc.client { "client process" } else { "process" };
+                    let what = format!("{} {} (PID: {})", kind, name, pid);
+                    let owner = || if pid == proc.pid {
+                        proc.user.clone()
+                    } else {
+                        process_owner(pid).map(|(_, user)| user).unwrap_or_else(|| "another user".to_string())
+                    };
+
+                    // Known to fail without privileges; don't even try
+                    let privileged = needs_privileges(pid, uid);
+                    if privileged && !config.sudo {
+                        failed += 1;
+                        eprintln!("{}", config.paint_err(&permission_hint(&what, &owner()), style::RED));
+                        continue;
+                    }
+                    match kill_process(pid, config.signal, privileged) {
+                        Ok(()) => {
+                            succeeded += 1;
+                            report_signal(true, &what, &config, terminating);
+                        }
+                        Err(e) if e.contains("not permitted") && !privileged => {
+                            failed += 1;
+                            eprintln!("{}", config.paint_err(&permission_hint(&what, &owner()), style::RED));
+                        }
+                        Err(_) => {
+                            failed += 1;
+                            report_signal(false, &what, &config, terminating);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if !config.quiet {
+        let processes = if matched == 1 { "process" } else { "processes" };
+        let ports = if port_processes.len() == 1 { "port" } else { "ports" };
+        if config.list_only {
+            println!("{} {} matched across {} {}",
+                     matched, processes, port_processes.len(), ports);
+        } else {
+            println!("{} {} matched, {} {}, {} failed, {} protected across {} {}",
+                     matched, processes, succeeded,
+                     if terminating { "terminated" } else { "signaled" },
+                     failed, protected, port_processes.len(), ports);
+        }
+    }
+
+    // Scripts must be able to tell that nothing was touched
+    if !config.list_only && protected == matched {
+        eprintln!("Error: All matching processes are protected or unconfirmed; nothing was signaled");
+        exit(2);
+    }
+
+    // A zero exit from kill doesn't mean the port is free: the process may
+    // ignore the signal or be respawned by a supervisor. Signals that don't
+    // terminate are only followed up when --wait asks for it.
+    if !config.list_only && config.verify && (terminating || config.wait) {
+        const VERIFY_GRACE: Duration = Duration::from_secs(1);
+        let window = if config.wait { config.timeout } else { VERIFY_GRACE };
+
+        // Ports held only by protected processes would never become free
+        let ports = targeted;
+        let mut busy = wait_for_ports(&ports, &config, window);
+
+        // Processes that ignored the signal get SIGKILL and one more window
+        if !busy.is_empty() && config.escalate && config.signal != "KILL" {
+            for port in &busy {
+                for proc in &port_processes[port] {
+                    if protection(proc.pid, &proc.name, &config).is_some() || declined.contains(&proc.pid)
+                        || !process_alive(proc.pid) {
+                        continue;
+                    }
+                    if !config.quiet {
+                        println!("Process {} (PID: {}) survived SIG{}, sending SIGKILL",
+                                 proc.name, proc.pid, config.signal);
+                    }
+                    let sudo = config.sudo && needs_privileges(proc.pid, uid);
+                    if kill_process(proc.pid, "KILL", sudo).is_err() {
+                        let line = format!("Failed to kill process {} (PID: {})", proc.name, proc.pid);
+                        eprintln!("{}", config.paint_err(&line, style::RED));
+                    }
+                }
+            }
+            busy = wait_for_ports(&busy, &config, window);
+        }
+
+        if !config.quiet {
+            for &port in &ports {
+                if !busy.contains(&port) {
+                    println!("Port {}: {}", config.paint(&port_label(port, &config), style::CYAN),
+                             config.paint("free", style::GREEN));
+                    continue;
                 }
+                // Who holds it now; a PID that wasn't there before was started anew
+                let holders: Vec<String> = get_processes_by_port(port, config.backend, config.protocols,
+                                                                 config.states)
+                    .iter()
+                    .map(|holder| {
+                        let respawned = !port_processes[&port].iter().any(|p| p.pid == holder.pid);
+                        format!("{} (PID {}{})", holder.name, holder.pid,
+                                if respawned { ", respawned" } else { "" })
+                    })
+                    .collect();
+                println!("Port {}: {}", config.paint(&port_label(port, &config), style::CYAN),
+                         config.paint(&format!("still in use by {}", holders.join(", ")), style::RED));
             }
         }
+        if !busy.is_empty() {
+            let busy: Vec<String> = busy.iter().map(|&port| port_label(port, &config)).collect();
+            eprintln!("Error: Port {} still in use after {:.1}s",
+                      busy.join(", "), window.as_secs_f64());
+            exit(1);
+        }
+    }
+
+    if failed > 0 {
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, name: &str, protocol: &str) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: name.to_string(),
+            user: "alice".to_string(),
+            protocol: protocol.to_string(),
+            state: "LISTEN".to_string(),
+            client: false,
+            local_addr: "0.0.0.0:8080".to_string(),
+            cpu: None,
+            rss: None,
+        }
+    }
+
+    #[test]
+    fn output_order_is_by_port_then_pid() {
+        // Ports and processes arrive in whatever order the backend has them
+        let found = [
+            (8080, vec![process(900, "node", "tcp"), process(12, "nginx", "udp"), process(12, "nginx", "tcp")]),
+            (80, vec![process(300, "httpd", "tcp"), process(7, "httpd", "tcp")]),
+            (443, vec![process(5, "envoy", "tcp")]),
+        ];
+        let mut port_processes = BTreeMap::new();
+        for (port, mut processes) in found {
+            sort_processes(&mut processes);
+            port_processes.insert(port, processes);
+        }
+
+        let order: Vec<(u16, u32, &str)> = port_processes.iter()
+            .flat_map(|(&port, processes)| processes.iter()
+                .map(move |proc| (port, proc.pid, proc.protocol.as_str())))
+            .collect();
+        assert_eq!(order, [
+            (80, 7, "tcp"), (80, 300, "tcp"),
+            (443, 5, "tcp"),
+            (8080, 12, "tcp"), (8080, 12, "udp"), (8080, 900, "tcp"),
+        ]);
+    }
+
+    #[test]
+    fn command_names_keep_spaces_and_brackets() {
+        assert_eq!(comm_name("my server\n"), "my server");
+        assert_eq!(comm_name("trailing \n"), "trailing ");
+        assert_eq!(comm_name("kworker/0:1-events\n"), "kworker/0:1-events");
+        assert_eq!(comm_name("\n"), "?");
+        assert_eq!(comm_name(""), "?");
+
+        assert_eq!(ps_value("my server      \n").as_deref(), Some("my server"));
+        assert_eq!(ps_value("[kworker/u8:2 ext4]\n").as_deref(), Some("[kworker/u8:2 ext4]"));
+        assert_eq!(ps_value("  \n"), None);
+    }
+
+    #[test]
+    fn users_resolve_through_passwd() {
+        let status = "Name:\tmy server\nUmask:\t0022\nState:\tS (sleeping)\n\
+                      Uid:\t1000\t1000\t1000\t1000\nGid:\t1000\t1000\t1000\t1000\n";
+        assert_eq!(status_uid(status).as_deref(), Some("1000"));
+        assert_eq!(status_uid("Name:\tkthreadd\n"), None);
+
+        let passwd = "root:x:0:0:root:/root:/bin/bash\n\
+                      alice:x:1000:1000:Alice:/home/alice:/bin/sh\n\
+                      :x:1001:1001::/nonexistent:/usr/sbin/nologin\n";
+        assert_eq!(passwd_user(passwd, "0").as_deref(), Some("root"));
+        assert_eq!(passwd_user(passwd, "1000").as_deref(), Some("alice"));
+        // No name, or no entry at all: the UID is shown instead
+        assert_eq!(passwd_user(passwd, "1001"), None);
+        assert_eq!(passwd_user(passwd, "4242"), None);
+    }
+
+    #[test]
+    fn own_process_has_name_and_user() {
+        let (name, user) = process_owner(std::process::id()).unwrap();
+        assert!(!name.is_empty() && name != "?");
+        assert!(!user.is_empty() && user != "?");
+    }
+
+    // Port, PID, protocol, state, client and local address of each socket
+    fn summary(processes: &[ProcessInfo]) -> Vec<(u32, &str, &str, bool, &str)> {
+        processes.iter()
+            .map(|proc| (proc.pid, proc.protocol.as_str(), proc.state.as_str(), proc.client,
+                         proc.local_addr.as_str()))
+            .collect()
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parses_ss_output() {
+        // PIDs have to exist for their owner to be looked up
+        let pid = std::process::id();
+        let output = format!("\
+Netid State  Recv-Q Send-Q Local Address:Port  Peer Address:Port Process
+tcp   LISTEN 0      511          0.0.0.0:8080       0.0.0.0:*     users:((\"node\",pid={pid},fd=20))
+udp   UNCONN 0      0      127.0.0.53%lo:53         0.0.0.0:*     users:((\"systemd-resolve\",pid={pid},fd=13))
+tcp   ESTAB  0      0          127.0.0.1:51234    127.0.0.1:8080  users:((\"my server\",pid={pid},fd=5))
+tcp   LISTEN 0      128          0.0.0.0:22         0.0.0.0:*     users:((\"sshd\",pid={pid},fd=3))
+tcp   LISTEN 0      128          0.0.0.0:18080      0.0.0.0:*
+", pid = pid);
+        let both = Protocols { tcp: true, udp: true };
+        assert_eq!(summary(&parse_ss(&output, 8080, both)), [
+            (pid, "tcp", "LISTEN", false, "0.0.0.0:8080"),
+            (pid, "tcp", "ESTAB", true, "127.0.0.1:51234"),
+        ]);
+        assert_eq!(summary(&parse_ss(&output, 53, both)), [
+            (pid, "udp", "UNCONN", false, "127.0.0.53%lo:53"),
+        ]);
+
+        // Asked for a single protocol, ss leaves out the Netid column
+        let output = format!("\
+State  Recv-Q Send-Q Local Address:Port Peer Address:Port Process
+LISTEN 0      4096            [::]:8080          [::]:*     users:((\"java\",pid={pid},fd=9))
+", pid = pid);
+        let tcp = Protocols { tcp: true, udp: false };
+        assert_eq!(summary(&parse_ss(&output, 8080, tcp)), [(pid, "tcp", "LISTEN", false, "[::]:8080")]);
+    }
+
+    #[test]
+    fn parses_lsof_output() {
+        let output = "\
+COMMAND        PID   USER   FD   TYPE DEVICE SIZE/OFF NODE NAME
+node          1234  alice   20u  IPv4  12345      0t0  TCP *:8080 (LISTEN)
+my\\x20server  1240    bob    7u  IPv6  23456      0t0  TCP [::1]:8080->[::1]:50000 (ESTABLISHED)
+curl          1300  alice    5u  IPv4  34567      0t0  TCP 127.0.0.1:50001->127.0.0.1:8080 (ESTABLISHED)
+dnsmasq        999 nobody    4u  IPv4  45678      0t0  UDP *:8080
+sshd           800   root    3u  IPv4  56789      0t0  TCP *:22 (LISTEN)
+";
+        let processes = parse_lsof(output, 8080);
+        assert_eq!(summary(&processes), [
+            (1234, "tcp", "LISTEN", false, "*:8080"),
+            (1240, "tcp", "ESTAB", false, "[::1]:8080"),
+            (1300, "tcp", "ESTAB", true, "127.0.0.1:50001"),
+            (999, "udp", "UNCONN", false, "*:8080"),
+        ]);
+        let owners: Vec<(&str, &str)> = processes.iter()
+            .map(|proc| (proc.name.as_str(), proc.user.as_str()))
+            .collect();
+        assert_eq!(owners, [("node", "alice"), ("my server", "bob"), ("curl", "alice"), ("dnsmasq", "nobody")]);
+    }
+
+    fn netstat_summary(sockets: &[NetstatSocket]) -> Vec<(&str, &str, &str, &str, Option<u32>)> {
+        sockets.iter()
+            .map(|socket| (socket.protocol.as_str(), socket.state.as_str(), socket.local.as_str(),
+                           socket.peer.as_str(), socket.pid))
+            .collect()
+    }
+
+    #[test]
+    fn parses_linux_netstat_output() {
+        let output = "\
+Active Internet connections (servers and established)
+Proto Recv-Q Send-Q Local Address           Foreign Address         State       PID/Program name
+tcp        0      0 0.0.0.0:8080            0.0.0.0:*               LISTEN      1234/node
+tcp        0      0 127.0.0.1:51234         127.0.0.1:8080          ESTABLISHED 1300/curl
+tcp6       0      0 :::443                  :::*                    LISTEN      -
+udp        0      0 127.0.0.53:53           0.0.0.0:*                           567/systemd-resolve
+udp        0      0 0.0.0.0:5353            0.0.0.0:*                           890/my server
+Active UNIX domain sockets (servers and established)
+Proto RefCnt Flags       Type       State         I-Node   PID/Program name     Path
+unix  2      [ ACC ]     STREAM     LISTENING     20314    1/init               /run/systemd/private
+";
+        assert_eq!(netstat_summary(&parse_netstat(output)), [
+            ("tcp", "LISTEN", "0.0.0.0:8080", "0.0.0.0:*", Some(1234)),
+            ("tcp", "ESTAB", "127.0.0.1:51234", "127.0.0.1:8080", Some(1300)),
+            ("tcp", "LISTEN", ":::443", ":::*", None),
+            ("udp", "UNCONN", "127.0.0.53:53", "0.0.0.0:*", Some(567)),
+            ("udp", "UNCONN", "0.0.0.0:5353", "0.0.0.0:*", Some(890)),
+        ]);
+    }
+
+    #[test]
+    fn parses_bsd_netstat_output() {
+        let output = "\
+Active Internet connections (including servers)
+Proto Recv-Q Send-Q  Local Address          Foreign Address        (state)
+tcp4       0      0  127.0.0.1.8080         *.*                    LISTEN
+tcp46      0      0  *.443                  *.*                    LISTEN
+tcp6       0      0  fe80::1%lo0.8080       *.*                    LISTEN
+udp4       0      0  *.5353                 *.*
+tcp4       0      0  192.168.1.5.50000      93.184.216.34.443      ESTABLISHED
+";
+        assert_eq!(netstat_summary(&parse_netstat(output)), [
+            ("tcp", "LISTEN", "127.0.0.1:8080", "*:*", None),
+            ("tcp", "LISTEN", "*:443", "*:*", None),
+            ("tcp", "LISTEN", "[fe80::1%lo0]:8080", "*:*", None),
+            ("udp", "UNCONN", "*:5353", "*:*", None),
+            ("tcp", "ESTAB", "192.168.1.5:50000", "93.184.216.34:443", None),
+        ]);
+    }
+
+    #[test]
+    fn cautions_about_foreign_and_old_processes() {
+        let day = Duration::from_secs(24 * 3600);
+        let hour = Duration::from_secs(3600);
+        assert_eq!(caution_reasons("alice", Some(1000), Some(hour), Some(1000), day), None);
+        assert_eq!(caution_reasons("root", Some(0), Some(hour), Some(1000), day).as_deref(),
+                   Some("owned by root"));
+        assert_eq!(caution_reasons("alice", Some(1000), Some(day * 3 + hour * 4), Some(1000), day).as_deref(),
+                   Some("running for 3d 4h"));
+        assert_eq!(caution_reasons("root", Some(0), Some(day * 2), Some(1000), day).as_deref(),
+                   Some("owned by root, running for 2d 0h"));
+        // Exactly at the threshold is not old yet
+        assert_eq!(caution_reasons("alice", Some(1000), Some(day), Some(1000), day), None);
+        // Root is warned about processes of other users as well
+        assert_eq!(caution_reasons("bob", Some(1001), None, Some(0), day).as_deref(), Some("owned by bob"));
+        // Nothing is flagged when the owner or age cannot be found
+        assert_eq!(caution_reasons("?", None, None, Some(1000), day), None);
+        assert_eq!(caution_reasons("bob", Some(1001), None, None, day), None);
+    }
+
+    #[test]
+    fn own_process_is_cautioned_only_past_the_threshold() {
+        let proc = process(std::process::id(), "test", "tcp");
+        let (real, _) = process_uids(proc.pid).unwrap();
+        let age = process_age(proc.pid);
+        assert!(age.is_some());
+        let long = Duration::from_secs(100 * 365 * 24 * 3600);
+        assert_eq!(caution_reasons(&proc.user, Some(real), age, Some(real), long), None);
+        assert_eq!(caution_reasons(&proc.user, Some(real), age, Some(real + 1), long).as_deref(),
+                   Some("owned by alice"));
+        // Just started, its age may still round down to zero
+        let reason = caution_reasons(&proc.user, Some(real), age.map(|age| age + Duration::from_secs(1)),
+                                     Some(real), Duration::ZERO);
+        assert!(reason.unwrap().starts_with("running for "));
+    }
+}